@@ -1,22 +1,39 @@
 package com.example.lancelot.execution.data
 
 import com.example.lancelot.execution.domain.ExecutionRepository
+import com.example.lancelot.execution.model.CompilerDiagnostic
 import com.example.lancelot.execution.model.ExecutionResult
 import com.example.lancelot.rust.RustBridge
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.withContext
+import org.json.JSONArray
 import org.json.JSONObject
 
 class RustExecutionRepository : ExecutionRepository {
     override suspend fun execute(code: String, language: String, input: String): ExecutionResult = withContext(Dispatchers.IO) {
+        // Los fallos llegan como excepciones tipadas (ExecutionException, ExecutionTimeoutException...)
         val json = RustBridge.executeCodeDetailed(code, language, input)
         val obj = JSONObject(json)
-        if (obj.has("error")) {
-            throw RuntimeException(obj.getString("error"))
-        }
         ExecutionResult(
             output = obj.optString("output"),
-            compileOutput = obj.optString("compile_output", null)
+            compileOutput = obj.optString("compile_output", null),
+            diagnostics = parseDiagnostics(obj.optJSONArray("diagnostics"))
         )
     }
+
+    private fun parseDiagnostics(array: JSONArray?): List<CompilerDiagnostic> {
+        if (array == null) return emptyList()
+        return (0 until array.length()).map { i ->
+            val d = array.getJSONObject(i)
+            val notes = d.optJSONArray("notes")
+            CompilerDiagnostic(
+                file = if (d.isNull("file")) null else d.getString("file"),
+                line = if (d.isNull("line")) null else d.getInt("line"),
+                column = if (d.isNull("column")) null else d.getInt("column"),
+                severity = d.getString("severity"),
+                message = d.getString("message"),
+                notes = if (notes == null) emptyList() else (0 until notes.length()).map { notes.getString(it) }
+            )
+        }
+    }
 }
@@ -2,5 +2,16 @@ package com.example.lancelot.execution.model
 
 data class ExecutionResult(
     val output: String,
-    val compileOutput: String?
+    val compileOutput: String?,
+    val diagnostics: List<CompilerDiagnostic> = emptyList()
+)
+
+/** Error o aviso del compilador; `line` y `column` son 1-based. */
+data class CompilerDiagnostic(
+    val file: String?,
+    val line: Int?,
+    val column: Int?,
+    val severity: String,
+    val message: String,
+    val notes: List<String>
 )
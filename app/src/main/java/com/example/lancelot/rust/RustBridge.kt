@@ -4,11 +4,16 @@ import android.util.Log
 import com.example.lancelot.common.RustResult
 import kotlinx.coroutines.sync.Mutex
 import kotlinx.coroutines.sync.withLock
+import java.nio.ByteBuffer
 import java.util.concurrent.CompletableFuture
 
 object RustBridge {
     private const val TAG = "RustBridge"
     private const val MAX_TEXT_LENGTH = 100_000
+
+    // Unidad de los offsets que reciben y devuelven las funciones con `offsetEncoding`; otro valor lanza IllegalArgumentException
+    const val OFFSET_UTF8 = 0
+    const val OFFSET_UTF16 = 1
     private val initMutex = Mutex()
     private var isInitialized = false
     
@@ -135,11 +140,254 @@ object RustBridge {
         hn: String
     ) : String
 
+    /**
+     * Como [highlight], con opciones JSON:
+     * `{"offset_encoding": "utf16", "include_points": true}` devuelve offsets en unidades
+     * UTF-16 (las de los String de Kotlin) y posiciones fila/columna.
+     * `"style_output": "argb"` añade a cada rango el color resuelto con el tema `t`;
+     * `"ids"` añade un id de estilo y la tabla `styles` completa.
+     */
+    external fun highlightWithOptions(
+        code: String,
+        languageName: String,
+        h: String,
+        i: String,
+        l: String,
+        t: String,
+        hn: String,
+        optionsJson: String
+    ) : String
+
+    /** Registra los nombres de resaltado una sola vez; el id se usa en las variantes empaquetadas. */
+    external fun registerHighlightNames(hn: String) : Int
+
+    /**
+     * Estilo de cada nombre registrado como tripletas (foreground, background, flags) ARGB.
+     * flags: 1 negrita, 2 cursiva, 4 subrayado, 8 hay foreground, 16 hay background; sin
+     * esos bits el color vale 0 y el tema no lo define (un 0 con el bit es `#00000000`).
+     */
+    external fun resolveThemeStyles(t: String, namesId: Int) : IntArray
+
+    /**
+     * Valida un tema y devuelve una lista JSON de problemas con `kind` (json_syntax,
+     * invalid_structure, invalid_color, unknown_capture, missing_base_color), `severity`,
+     * `message`, y `key` o `line`/`column` según el caso. Una lista vacía indica que es válido.
+     */
+    external fun validateTheme(t: String) : String
+
+    /**
+     * Convierte un tema de VS Code (JSON), TextMate (.tmTheme) o Helix (TOML) al JSON de tema
+     * propio. `format` puede ser "vscode", "textmate", "helix" o vacío para detectarlo.
+     * La respuesta incluye `unknown_scopes` con lo que no se pudo mapear.
+     */
+    external fun importTheme(source: String, format: String) : String
+
+    /** Rangos del documento abierto como tripletas (start, end, type) consecutivas. */
+    external fun highlightPacked(handle: Long, offsetEncoding: Int) : IntArray
+
+    /**
+     * Escribe las tripletas en un ByteBuffer directo con `order(ByteOrder.nativeOrder())`.
+     * Devuelve los rangos escritos, o `-n` si el buffer no tiene espacio para `n` rangos.
+     */
+    external fun highlightIntoBuffer(handle: Long, offsetEncoding: Int, buffer: ByteBuffer) : Int
+
+    /** Abre un documento nativo que conserva texto y árbol; liberar con [closeDocument]. */
+    external fun openDocument(
+        languageName: String,
+        code: String,
+        h: String,
+        i: String,
+        l: String,
+        namesId: Int
+    ) : Long
+
+    external fun updateDocument(handle: Long, code: String)
+
+    external fun closeDocument(handle: Long)
+
+    /** Tripletas (start, end, type) sólo para el viewport `[start, end)`, en `offsetEncoding`. */
+    external fun highlightRange(handle: Long, start: Int, end: Int, offsetEncoding: Int) : IntArray
+
+    /**
+     * Exporta el documento coloreado. `format`: "html", "ansi" o "rtf".
+     * `optionsJson` (vacío = por defecto): css_classes, standalone, title, font_family, font_size.
+     */
+    external fun exportHighlighted(handle: Long, format: String, themeJson: String, optionsJson: String) : String
+
+    /**
+     * Errores de sintaxis del documento en JSON: kind ("missing"/"unexpected"), start, end,
+     * start_point, end_point y message. Offsets según `offsetEncoding`.
+     */
+    external fun syntaxDiagnostics(handle: Long, offsetEncoding: Int) : String
+
+    /**
+     * Outline del documento en JSON: name, kind, start, end, name_start, name_end,
+     * start_point y children. Offsets según `offsetEncoding`.
+     */
+    external fun documentSymbols(handle: Long, offsetEncoding: Int) : String
+
+    /** Selección `[start, end]` ampliada al siguiente nodo sintáctico que la contiene. */
+    external fun expandSelection(handle: Long, start: Int, end: Int, offsetEncoding: Int) : IntArray
+
+    /** Selección `[start, end]` reducida: deshace la última ampliación o baja a un nodo hijo. */
+    external fun shrinkSelection(handle: Long, start: Int, end: Int, offsetEncoding: Int) : IntArray
+
+    /** `[openStart, openEnd, closeStart, closeEnd]` del corchete en `offset`, o vacío si no hay pareja. */
+    external fun matchBracket(handle: Long, offset: Int, offsetEncoding: Int) : IntArray
+
+    /** Tripletas `(start, end, depth)` de los corchetes del rango; `depth = -1` si está desemparejado. */
+    external fun bracketDepths(handle: Long, start: Int, end: Int, offsetEncoding: Int) : IntArray
+
+    /**
+     * Completados para el cursor en JSON: prefix_start, prefix_end e items (label, kind,
+     * detail, score) ya ordenados. Offsets según `offsetEncoding`.
+     */
+    external fun completions(handle: Long, offset: Int, offsetEncoding: Int, limit: Int) : String
+
+    /** Carga un paquete de snippets en formato VS Code para `language` (o los de su `scope`). */
+    external fun loadSnippetPack(language: String, packJson: String) : Int
+
+    /** Snippets cargados que encajan con `prefix`, mejor puntuados primero, en JSON. */
+    external fun snippetSuggestions(language: String, prefix: String) : String
+
+    /**
+     * Expande un snippet con la gramática de LSP. `contextJson` (vacío = por defecto) admite
+     * `variables` (TM_FILENAME...) e `indent`. Devuelve `text` y `tab_stops` en orden de recorrido.
+     */
+    external fun expandSnippet(body: String, contextJson: String, offsetEncoding: Int) : String
+
+    /** Indexa un directorio de plantillas (.cpp/.py/.js); devuelve sus resúmenes en JSON. */
+    external fun indexTemplates(directory: String) : String
+
+    /** Registra una plantilla; `name` se usa si el contenido no declara `// @name`. */
+    external fun registerTemplate(language: String, name: String, content: String)
 
+    /** Plantillas registradas para `language` (name, description, requires, headers) en JSON. */
+    external fun listTemplates(language: String) : String
+
+    /**
+     * Edits para insertar la plantilla `name` con sus dependencias antes de la declaración que
+     * contiene `offset`, más los `#include`/`import` que falten. No modifica el documento:
+     * se aplican con [applyEdits].
+     */
+    external fun insertTemplate(handle: Long, name: String, offset: Int, offsetEncoding: Int) : String
+
+    /**
+     * Formatea el documento con clang-format/black/prettier si están disponibles o, si no,
+     * reindentando con el árbol. `optionsJson` (vacío = por defecto): `indent_width`,
+     * `use_tabs`, `external`, `insert_final_newline`. Devuelve `edits` mínimos y `formatter`.
+     */
+    external fun format(handle: Long, optionsJson: String, offsetEncoding: Int) : String
+
+    /**
+     * Reemplaza `[start, end)` por `text` en el buffer nativo del documento sin mandar el
+     * texto completo. Devuelve la nueva versión.
+     */
+    external fun editDocument(handle: Long, start: Int, end: Int, text: String, offsetEncoding: Int) : Long
+
+    /** Aplica edits JSON `[{start, end, text}]` no solapados con un único reparseo; devuelve la versión. */
+    external fun applyEdits(handle: Long, editsJson: String, offsetEncoding: Int) : Long
+
+    /** `[line, column]` 0-based de `offset`. */
+    external fun positionAt(handle: Long, offset: Int, offsetEncoding: Int) : IntArray
+
+    /** Offset de `line`/`column` (0-based), limitado al final de la línea. */
+    external fun offsetAt(handle: Long, line: Int, column: Int, offsetEncoding: Int) : Int
+
+    /** Texto actual del buffer nativo. */
+    external fun documentText(handle: Long) : String
+
+    /**
+     * Deshace la última transacción del historial nativo. Devuelve los edits JSON aplicados en
+     * orden (cada uno sobre el texto que deja el anterior); `[]` si no hay nada que deshacer.
+     */
+    external fun undo(handle: Long, offsetEncoding: Int) : String
+
+    /** Rehace la última transacción deshecha; mismo formato que [undo]. */
+    external fun redo(handle: Long, offsetEncoding: Int) : String
+
+    /** Agrupa las ediciones hasta [endUndoGroup]: typing, delete, paste, snippet, format u other. */
+    external fun beginUndoGroup(handle: Long, kind: String)
+
+    external fun endUndoGroup(handle: Long)
+
+    /** Evita que la próxima pulsación se agrupe con la anterior (p. ej. al mover el cursor). */
+    external fun breakUndoGroup(handle: Long)
+
+    /** `[undoCount, redoCount]`. */
+    external fun historyState(handle: Long) : IntArray
+
+    /** Historial serializado para persistirlo junto al archivo. */
+    external fun saveHistory(handle: Long) : String
+
+    /** Restaura un historial de [saveHistory]; lanza una excepción si el texto ya no coincide. */
+    external fun restoreHistory(handle: Long, historyJson: String)
+
+    /**
+     * Aplica una edición (`{"type":"insert"|"replace","text":...}`, `{"type":"delete_backward"}` o
+     * `{"type":"delete_forward"}`) en todas las selecciones `[{anchor, head}]` a la vez, como una
+     * sola transacción de deshacer. Devuelve `edits` sobre el texto anterior y las nuevas `selections`.
+     */
+    external fun editSelections(handle: Long, selectionsJson: String, editJson: String, offsetEncoding: Int) : String
+
+    /** Añade la siguiente aparición de la última selección (o amplía el cursor a su palabra). */
+    external fun addNextOccurrence(handle: Long, selectionsJson: String, offsetEncoding: Int) : String
+
+    /**
+     * Busca en el documento. `queryJson`: `pattern`, `regex`, `whole_word`, `case_sensitive` y
+     * `scope` (all, code, comments, strings). Devuelve hasta `limit` `matches` (0 = todos) desde
+     * `from` y `next`, el offset de la siguiente tanda o null.
+     */
+    external fun findInDocument(handle: Long, queryJson: String, from: Int, limit: Int, offsetEncoding: Int) : String
+
+    /** Edits JSON que reemplazan todas las coincidencias; se aplican con [applyEdits]. */
+    external fun replaceAll(handle: Long, queryJson: String, replacement: String, offsetEncoding: Int) : String
+
+    /**
+     * Prueba una query de tree-sitter sobre el documento. Si no compila devuelve
+     * `{"status":"error","error":{row, column, offset, kind, message}}`; si no, todas las
+     * capturas (`name`, `node_kind`, rango y texto) en `{"status":"ok","captures":[...]}`.
+     */
+    external fun runQuery(handle: Long, querySource: String, offsetEncoding: Int) : String
+
+    /**
+     * Diagnósticos estructurados de la salida de g++/clang, javac, rustc, kotlinc o Python.
+     * `line` y `column` son 1-based; la columna se interpreta en bytes UTF-8.
+     */
+    external fun parseCompilerDiagnostics(output: String) : String
+
+    /**
+     * Como [parseCompilerDiagnostics], con `offset` (en `offsetEncoding`) en los diagnósticos
+     * de `fileName`, el archivo con el que se compiló el documento abierto.
+     */
+    external fun parseDocumentDiagnostics(handle: Long, output: String, fileName: String, offsetEncoding: Int) : String
+
+    /** Leyenda LSP (`tokenTypes`, `tokenModifiers`) derivada de los nombres registrados. */
+    external fun semanticTokensLegend(namesId: Int) : String
+
+    /** `SemanticTokens` de LSP en JSON (`resultId`, `data` relativo con columnas UTF-16). */
+    external fun semanticTokens(handle: Long) : String
+
+    /** `SemanticTokensDelta` respecto a `previousResultId`, o tokens completos si ya no está disponible. */
+    external fun semanticTokensDelta(handle: Long, previousResultId: String) : String
+
+    // Variantes bloqueantes: esperan la tarea lanzada en el runtime compartido de Rust
     external fun executeCode(code: String, languageName: String, input: String) : String
 
     external fun executeCodeDetailed(code: String, languageName: String, input: String) : String
 
+    external fun executeCodeAsync(
+        code: String,
+        languageName: String,
+        input: String
+    ) : CompletableFuture<String>
+
+    external fun executeCodeDetailedAsync(
+        code: String,
+        languageName: String,
+        input: String
+    ) : CompletableFuture<String>
+
     external fun ktFuture(
         code: String,
         languageName: String,
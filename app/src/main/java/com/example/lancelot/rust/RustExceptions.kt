@@ -0,0 +1,14 @@
+package com.example.lancelot.rust
+
+/** Base de las excepciones lanzadas por la librería nativa. */
+open class RustException(message: String) : RuntimeException(message)
+
+class ParseException(message: String) : RustException(message)
+
+class UnsupportedLanguageException(message: String) : RustException(message)
+
+class InvalidQueryException(message: String) : RustException(message)
+
+open class ExecutionException(message: String) : RustException(message)
+
+class ExecutionTimeoutException(message: String) : ExecutionException(message)
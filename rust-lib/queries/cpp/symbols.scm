@@ -0,0 +1,56 @@
+; Definiciones para el outline del documento: @definition.<tipo> marca el nodo completo
+; y @name el nombre que se muestra
+
+(namespace_definition
+  name: (_) @name) @definition.namespace
+
+(class_specifier
+  name: (_) @name
+  body: (_)) @definition.class
+
+(struct_specifier
+  name: (_) @name
+  body: (_)) @definition.struct
+
+(union_specifier
+  name: (_) @name
+  body: (_)) @definition.struct
+
+(enum_specifier
+  name: (_) @name
+  body: (_)) @definition.enum
+
+(function_definition
+  declarator: (function_declarator
+    declarator: (_) @name)) @definition.function
+
+(function_definition
+  declarator: (reference_declarator
+    (function_declarator
+      declarator: (_) @name))) @definition.function
+
+(function_definition
+  declarator: (pointer_declarator
+    declarator: (function_declarator
+      declarator: (_) @name))) @definition.function
+
+(type_definition
+  declarator: (type_identifier) @name) @definition.type
+
+(alias_declaration
+  name: (type_identifier) @name) @definition.type
+
+(preproc_def
+  name: (identifier) @name) @definition.constant
+
+(preproc_function_def
+  name: (identifier) @name) @definition.macro
+
+(translation_unit
+  (declaration
+    declarator: (init_declarator
+      declarator: (identifier) @name)) @definition.variable)
+
+(translation_unit
+  (declaration
+    declarator: (identifier) @name) @definition.variable)
@@ -0,0 +1,28 @@
+; Definiciones para el outline del documento: @definition.<tipo> marca el nodo completo
+; y @name el nombre que se muestra
+
+(class_declaration
+  name: (_) @name) @definition.class
+
+(method_definition
+  name: (_) @name) @definition.method
+
+(function_declaration
+  name: (identifier) @name) @definition.function
+
+(generator_function_declaration
+  name: (identifier) @name) @definition.function
+
+(variable_declarator
+  name: (identifier) @name
+  value: [(arrow_function) (function_expression)]) @definition.function
+
+(program
+  (lexical_declaration
+    (variable_declarator
+      name: (identifier) @name) @definition.variable))
+
+(program
+  (variable_declaration
+    (variable_declarator
+      name: (identifier) @name) @definition.variable))
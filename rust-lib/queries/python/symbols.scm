@@ -0,0 +1,13 @@
+; Definiciones para el outline del documento: @definition.<tipo> marca el nodo completo
+; y @name el nombre que se muestra
+
+(class_definition
+  name: (identifier) @name) @definition.class
+
+(function_definition
+  name: (identifier) @name) @definition.function
+
+(module
+  (expression_statement
+    (assignment
+      left: (identifier) @name) @definition.variable))
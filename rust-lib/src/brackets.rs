@@ -0,0 +1,175 @@
+use std::ops::Range;
+use tree_sitter::{Node, Tree};
+
+/// Pareja de corchetes; cada extremo es el rango en bytes del token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketPair {
+    pub open: Range<usize>,
+    pub close: Range<usize>,
+}
+
+/// Corchete visible con su profundidad de anidamiento (para colorearlos en arcoíris).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bracket {
+    pub range: Range<usize>,
+    pub depth: usize,
+    pub matched: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Open,
+    Close,
+}
+
+// Sólo cuentan los tokens anónimos del árbol: los de cadenas y comentarios no son nodos
+fn bracket_side(node: &Node) -> Option<Side> {
+    if node.is_named() || node.is_missing() || node.child_count() > 0 {
+        return None;
+    }
+    match node.kind() {
+        "(" | "[" | "{" | "${" => Some(Side::Open),
+        ")" | "]" | "}" => Some(Side::Close),
+        _ => None,
+    }
+}
+
+fn closes(open: &str, close: &str) -> bool {
+    matches!((open, close), ("(", ")") | ("[", "]") | ("{", "}") | ("${", "}"))
+}
+
+/// Pareja del corchete bajo el cursor; si no hay ninguno en `offset`, prueba con el
+/// carácter anterior (cursor justo detrás de un corchete).
+pub fn match_bracket(tree: &Tree, offset: usize) -> Option<BracketPair> {
+    let bracket = bracket_at(tree, offset)
+        .or_else(|| offset.checked_sub(1).and_then(|previous| bracket_at(tree, previous)))?;
+    let partner = partner(&bracket)?;
+    let (open, close) = match bracket_side(&bracket)? {
+        Side::Open => (bracket, partner),
+        Side::Close => (partner, bracket),
+    };
+    Some(BracketPair { open: open.byte_range(), close: close.byte_range() })
+}
+
+fn bracket_at<'t>(tree: &'t Tree, offset: usize) -> Option<Node<'t>> {
+    let node = tree.root_node().descendant_for_byte_range(offset, offset + 1)?;
+    (node.start_byte() == offset && bracket_side(&node).is_some()).then_some(node)
+}
+
+// Busca entre los hermanos, contando los corchetes del mismo tipo que se abren por el camino
+fn partner<'t>(bracket: &Node<'t>) -> Option<Node<'t>> {
+    let side = bracket_side(bracket)?;
+    let mut depth = 0usize;
+    let mut sibling = *bracket;
+    loop {
+        sibling = match side {
+            Side::Open => sibling.next_sibling()?,
+            Side::Close => sibling.prev_sibling()?,
+        };
+        let Some(sibling_side) = bracket_side(&sibling) else {
+            continue;
+        };
+        let (open, close) = match side {
+            Side::Open => (bracket.kind(), sibling.kind()),
+            Side::Close => (sibling.kind(), bracket.kind()),
+        };
+        if sibling_side == side && sibling.kind() == bracket.kind() {
+            depth += 1;
+        } else if sibling_side != side && closes(open, close) {
+            if depth == 0 {
+                return Some(sibling);
+            }
+            depth -= 1;
+        }
+    }
+}
+
+/// Corchetes que intersecan `range` en orden, con su profundidad absoluta en el documento.
+///
+/// Recorre las hojas en orden de documento con una pila de aperturas: la profundidad es la
+/// altura de la pila y un cierre empareja con la apertura de la cima si es de su tipo. El
+/// recorrido empieza en el inicio del documento y sigue tras `range` sólo mientras queden
+/// aperturas del rango sin cerrar.
+pub fn brackets_in_range(tree: &Tree, range: Range<usize>) -> Vec<Bracket> {
+    let mut brackets: Vec<Bracket> = Vec::new();
+    // Aperturas pendientes: (tipo, índice en `brackets` si está dentro del rango)
+    let mut open: Vec<(&str, Option<usize>)> = Vec::new();
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        if node.start_byte() >= range.end && open.iter().all(|(_, index)| index.is_none()) {
+            break;
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+
+        let in_range = node.end_byte() > range.start && node.start_byte() < range.end;
+        match bracket_side(&node) {
+            Some(Side::Open) => {
+                let index = in_range.then(|| {
+                    brackets.push(Bracket { range: node.byte_range(), depth: open.len(), matched: false });
+                    brackets.len() - 1
+                });
+                open.push((node.kind(), index));
+            }
+            Some(Side::Close) => {
+                let matched = open.last().is_some_and(|&(kind, _)| closes(kind, node.kind()));
+                if matched {
+                    if let Some((_, Some(index))) = open.pop() {
+                        brackets[index].matched = true;
+                    }
+                }
+                if in_range {
+                    brackets.push(Bracket { range: node.byte_range(), depth: open.len(), matched });
+                }
+            }
+            None => {}
+        }
+
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+    brackets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::highlighting::load_language;
+    use tree_sitter::Parser;
+
+    #[test]
+    fn matches_brackets_outside_literals() {
+        let code = "int main() { f(a[0], \")\"); /* } */ }";
+        let mut parser = Parser::new();
+        parser.set_language(&load_language("cpp").unwrap()).unwrap();
+        let tree = parser.parse(code, None).unwrap();
+
+        let open_call = code.find("f(").unwrap() + 1;
+        let pair = match_bracket(&tree, open_call).unwrap();
+        assert_eq!(&code[pair.close.end..pair.close.end + 1], ";");
+        // Cursor justo detrás del corchete de cierre
+        assert_eq!(match_bracket(&tree, pair.close.end), Some(pair));
+
+        let body = match_bracket(&tree, code.find('{').unwrap()).unwrap();
+        assert_eq!(body.close.start, code.len() - 1);
+
+        let brackets = brackets_in_range(&tree, 0..code.len());
+        let depths: Vec<(&str, usize)> = brackets.iter().map(|b| (&code[b.range.clone()], b.depth)).collect();
+        assert_eq!(depths, vec![
+            ("(", 0), (")", 0), ("{", 0), ("(", 1), ("[", 2), ("]", 2), (")", 1), ("}", 0),
+        ]);
+        assert!(brackets.iter().all(|b| b.matched));
+
+        let index = code.find("a[").unwrap();
+        let inner: Vec<(usize, usize, bool)> = brackets_in_range(&tree, index..index + 4).iter()
+            .map(|b| (b.range.start, b.depth, b.matched)).collect();
+        assert_eq!(inner, vec![(index + 1, 2, true), (index + 3, 2, true)]);
+        let unclosed = brackets_in_range(&parser.parse("f((x);", None).unwrap(), 0..6);
+        assert_eq!(unclosed.iter().map(|b| (b.depth, b.matched)).collect::<Vec<_>>(), vec![(0, false), (1, true), (1, true)]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::ops::Range;
+use ropey::Rope;
+use tree_sitter::{InputEdit, Point};
+
+use crate::offsets::{OffsetEncoding, TextPoint};
+
+/// Texto del documento en un rope: las ediciones y las conversiones byte ↔ UTF-16 ↔ línea
+/// son O(log n), así que Kotlin puede mandar sólo el tramo cambiado en cada pulsación.
+#[derive(Debug, Clone, Default)]
+pub struct TextBuffer {
+    rope: Rope,
+}
+
+impl TextBuffer {
+    pub fn new(text: &str) -> Self {
+        Self { rope: Rope::from_str(text) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rope.len_bytes() == 0
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> String {
+        self.rope.byte_slice(range).to_string()
+    }
+
+    /// Tramo contiguo que empieza en `byte`, para el callback de `Parser::parse_with_options`.
+    /// Vacío al final del texto, como espera tree-sitter.
+    pub fn chunk_at(&self, byte: usize) -> &[u8] {
+        if byte >= self.len() {
+            return &[];
+        }
+        let (chunk, chunk_start, _, _) = self.rope.chunk_at_byte(byte);
+        &chunk.as_bytes()[byte - chunk_start..]
+    }
+
+    /// Tramos del rope que cubren `range`. Sirve como `TextProvider` de `QueryCursor` para
+    /// leer nodos sin materializar el texto completo.
+    pub fn chunks(&self, range: Range<usize>) -> impl Iterator<Item = &str> {
+        let start = self.floor_char_boundary(range.start);
+        let end = self.floor_char_boundary(range.end).max(start);
+        self.rope.byte_slice(start..end).chunks()
+    }
+
+    // Ajusta `byte` al límite de carácter anterior para no cortar un carácter multibyte
+    fn floor_char_boundary(&self, byte: usize) -> usize {
+        let byte = byte.min(self.len());
+        self.rope.char_to_byte(self.rope.byte_to_char(byte))
+    }
+
+    /// Punto fila/columna en bytes, como lo espera tree-sitter.
+    pub fn point_at(&self, byte: usize) -> Point {
+        let byte = byte.min(self.len());
+        let row = self.rope.byte_to_line(byte);
+        Point::new(row, byte - self.rope.line_to_byte(row))
+    }
+
+    pub fn convert(&self, byte: usize, encoding: OffsetEncoding) -> usize {
+        match encoding {
+            OffsetEncoding::Utf8 => byte.min(self.len()),
+            OffsetEncoding::Utf16 => self.rope.char_to_utf16_cu(self.rope.byte_to_char(byte.min(self.len()))),
+        }
+    }
+
+    /// Inverso de `convert`: pasa un offset expresado en `encoding` a bytes UTF-8.
+    pub fn to_byte(&self, offset: usize, encoding: OffsetEncoding) -> usize {
+        match encoding {
+            OffsetEncoding::Utf8 => self.floor_char_boundary(offset),
+            OffsetEncoding::Utf16 => {
+                let offset = offset.min(self.rope.len_utf16_cu());
+                self.rope.char_to_byte(self.rope.utf16_cu_to_char(offset))
+            }
+        }
+    }
+
+    pub fn byte_to_point(&self, byte: usize, encoding: OffsetEncoding) -> TextPoint {
+        let byte = self.floor_char_boundary(byte);
+        let row = self.rope.byte_to_line(byte);
+        let line_start = self.rope.line_to_byte(row);
+        let column = self.convert(byte, encoding) - self.convert(line_start, encoding);
+        TextPoint { row, column }
+    }
+
+    /// Offset en bytes de una posición fila/columna; la columna se limita al final de la línea
+    /// (sin el salto) y la fila, a la última.
+    pub fn point_to_byte(&self, point: TextPoint, encoding: OffsetEncoding) -> usize {
+        let line_start = self.line_start(point.row);
+        let byte = self.to_byte(self.convert(line_start, encoding) + point.column, encoding);
+        byte.clamp(line_start, self.line_end(point.row))
+    }
+
+    /// Byte en que empieza la fila `row` (limitada a la última).
+    pub fn line_start(&self, row: usize) -> usize {
+        self.rope.line_to_byte(row.min(self.line_count() - 1))
+    }
+
+    /// Fin del contenido de la fila `row`, sin el salto de línea ni el `\r` de un CRLF.
+    pub fn line_end(&self, row: usize) -> usize {
+        let row = row.min(self.line_count() - 1);
+        let line = self.rope.line(row);
+        let mut content = line.len_chars();
+        while content > 0 && matches!(line.char(content - 1), '\n' | '\r') {
+            content -= 1;
+        }
+        self.rope.line_to_byte(row) + line.char_to_byte(content)
+    }
+
+    /// Reemplaza `range` (bytes) por `text` y devuelve el `InputEdit` para el árbol.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> InputEdit {
+        let start = self.floor_char_boundary(range.start);
+        let end = self.floor_char_boundary(range.end).max(start);
+        let start_position = self.point_at(start);
+        let old_end_position = self.point_at(end);
+
+        let start_char = self.rope.byte_to_char(start);
+        self.rope.remove(start_char..self.rope.byte_to_char(end));
+        self.rope.insert(start_char, text);
+
+        let new_end = start + text.len();
+        InputEdit {
+            start_byte: start,
+            old_end_byte: end,
+            new_end_byte: new_end,
+            start_position,
+            old_end_position,
+            new_end_position: self.point_at(new_end),
+        }
+    }
+}
+
+impl std::fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.rope.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_and_converts_offsets() {
+        let mut buffer = TextBuffer::new("let a = \"ñ😀\";\nb\n");
+        let b = buffer.to_string().find('b').unwrap();
+        assert_eq!(buffer.convert(b, OffsetEncoding::Utf16), 15);
+        assert_eq!(buffer.to_byte(15, OffsetEncoding::Utf16), b);
+        assert_eq!(buffer.byte_to_point(b, OffsetEncoding::Utf16), TextPoint { row: 1, column: 0 });
+        assert_eq!(buffer.point_to_byte(TextPoint { row: 0, column: 99 }, OffsetEncoding::Utf16), b - 1);
+        let quote = "let a = \"ñ😀".len();
+        assert_eq!(buffer.byte_to_point(quote, OffsetEncoding::Utf16), TextPoint { row: 0, column: 12 });
+        assert_eq!(buffer.byte_to_point(quote, OffsetEncoding::Utf8), TextPoint { row: 0, column: 15 });
+
+        let edit = buffer.edit(b..b + 1, "c\nd");
+        assert_eq!(buffer.to_string(), "let a = \"ñ😀\";\nc\nd\n");
+        assert_eq!(edit.old_end_position, Point::new(1, 1));
+        assert_eq!(edit.new_end_position, Point::new(2, 1));
+        assert_eq!(buffer.chunk_at(buffer.len()), b"");
+        // Un corte dentro de `ñ` retrocede al inicio del carácter
+        assert_eq!(buffer.chunks(10..15).collect::<String>(), "ñ😀");
+        assert_eq!(buffer.line_count(), 4);
+
+        let crlf = TextBuffer::new("a\r\nbc");
+        assert_eq!((crlf.line_end(0), crlf.line_start(1), crlf.line_end(9)), (1, 3, 5));
+    }
+}
@@ -1,20 +1,39 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Instant;
 use once_cell::sync::Lazy;
 use crate::types::{HighlightRange, HighlightDelta};
 use tree_sitter::Tree;
 use crate::text_utils::text_difference_ratio;
-use std::hash::{Hash, Hasher};
 use crate::theme::Theme;
+use log::warn;
+
+/// Bloqueo que sobrevive a un pánico previo: si otro hilo envenenó el mutex,
+/// se limpia la marca y se sigue usando el contenido.
+pub trait LockExt<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            warn!("Recovering poisoned cache mutex");
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
 
 // Cache para temas
 pub static THEME_CACHE: Lazy<Mutex<HashMap<u64, Arc<Theme>>>> = Lazy::new(|| {
     Mutex::new(HashMap::new())
 });
 
+// Árbol de sintaxis junto al texto que lo generó
+pub type ParseCacheEntry = (Arc<Tree>, String);
+
 // Cache para árboles de sintaxis
-pub static PARSE_CACHE: Lazy<Mutex<HashMap<u64, (Arc<Tree>, String)>>> = Lazy::new(|| {
+pub static PARSE_CACHE: Lazy<Mutex<HashMap<u64, ParseCacheEntry>>> = Lazy::new(|| {
     Mutex::new(HashMap::new())
 });
 
@@ -61,26 +80,38 @@ pub static HIGHLIGHT_CACHE: Lazy<Mutex<HashMap<String, Arc<HighlightCache>>>> =
 pub static PARSER_CACHE: Lazy<Mutex<HashMap<String, tree_sitter::Parser>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
-pub fn get_cached_theme(theme_str: &str) -> Arc<Theme> {
-    let mut hasher = std::collections::hash_map::DefaultHasher::new();
-    theme_str.hash(&mut hasher);
-    let input_hash = hasher.finish();
-    
-    if let Some(cached_theme) = THEME_CACHE.lock().unwrap().get(&input_hash) {
-        return Arc::clone(cached_theme);
+// Listas de nombres de resaltado registradas una sola vez desde Kotlin; el índice es su id
+pub static HIGHLIGHT_NAMES_REGISTRY: Lazy<Mutex<Vec<Arc<Vec<String>>>>> =
+    Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Registra una lista de nombres y devuelve su id. Registrar la misma lista dos veces
+/// devuelve el mismo id.
+pub fn register_highlight_names(names: Vec<String>) -> usize {
+    let mut registry = HIGHLIGHT_NAMES_REGISTRY.lock_or_recover();
+    if let Some(id) = registry.iter().position(|existing| **existing == names) {
+        return id;
     }
-    
-    let theme: Theme = serde_json::from_str(theme_str).unwrap_or(Theme { 
-        theme: HashMap::new()
-    });
-    
-    let theme_arc = Arc::new(theme);
-    THEME_CACHE.lock().unwrap().insert(input_hash, Arc::clone(&theme_arc));
-    theme_arc
+    registry.push(Arc::new(names));
+    registry.len() - 1
+}
+
+pub fn get_registered_highlight_names(id: usize) -> Option<Arc<Vec<String>>> {
+    HIGHLIGHT_NAMES_REGISTRY.lock_or_recover().get(id).cloned()
+}
+
+/// Todos los nombres registrados hasta ahora, sin repetir.
+pub fn all_registered_highlight_names() -> Vec<String> {
+    let mut names: Vec<String> = HIGHLIGHT_NAMES_REGISTRY.lock_or_recover()
+        .iter()
+        .flat_map(|list| list.iter().cloned())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
 }
 
 pub fn get_highlight_cache(language_name: &str) -> Option<Arc<HighlightCache>> {
-    HIGHLIGHT_CACHE.lock().unwrap().get(language_name).cloned()
+    HIGHLIGHT_CACHE.lock_or_recover().get(language_name).cloned()
 }
 
 pub fn update_highlight_cache(
@@ -91,8 +122,8 @@ pub fn update_highlight_cache(
     ranges: Vec<HighlightRange>,
     highlight_delta: HighlightDelta,
 ) {
-    let mut parse_cache = PARSE_CACHE.lock().unwrap();
-    let mut highlight_cache = HIGHLIGHT_CACHE.lock().unwrap();
+    let mut parse_cache = PARSE_CACHE.lock_or_recover();
+    let mut highlight_cache = HIGHLIGHT_CACHE.lock_or_recover();
 
     parse_cache.insert(input_hash, (Arc::clone(&new_tree), input.clone()));
     
@@ -104,4 +135,23 @@ pub fn update_highlight_cache(
     ));
     
     highlight_cache.insert(language_name.clone(), Arc::clone(&cache));
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_or_recover_survives_poisoned_mutex() {
+        let mutex = Arc::new(Mutex::new(1));
+        let poisoner = Arc::clone(&mutex);
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison");
+        }).join();
+
+        assert!(mutex.is_poisoned());
+        *mutex.lock_or_recover() += 1;
+        assert!(!mutex.is_poisoned());
+        assert_eq!(*mutex.lock_or_recover(), 2);
+    }
+}
@@ -1,17 +1,26 @@
+use std::time::Duration;
 use log::{debug, error};
 use serde::{Serialize, Deserialize};
+use crate::compiler_diagnostics::{parse_compiler_output, CompilerDiagnostic};
+use crate::error::{RunixError, RunixResult};
+
+// Tiempo máximo de espera por una respuesta de Piston
+pub const EXECUTION_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ExecutionResult {
     pub output: String,
     pub compile_output: Option<String>,
+    // Errores del compilador y trazas de stderr ya estructurados
+    #[serde(default)]
+    pub diagnostics: Vec<CompilerDiagnostic>,
 }
 
-pub async fn execute_code(
+async fn run_piston(
     code: &str,
     language_name: &str,
     input: &str,
-) -> Result<String, String> {
+) -> RunixResult<piston_rs::ExecResponse> {
     let client = piston_rs::Client::new();
     let executor = piston_rs::Executor::new()
         .set_language(language_name)
@@ -23,59 +32,47 @@ pub async fn execute_code(
                 .set_content(code),
         );
 
-    match client.execute(&executor).await {
-        Ok(response) => {
+    match tokio::time::timeout(EXECUTION_TIMEOUT, client.execute(&executor)).await {
+        Ok(Ok(response)) => {
             debug!("Language: {}", response.language);
             debug!("Version: {}", response.version);
-            
-            if let Some(c) = response.compile {
+
+            if let Some(ref c) = response.compile {
                 debug!("Compilation: {}", c.output);
             }
 
             debug!("Output: {}", response.run.output);
-            Ok(response.run.output)
+            Ok(response)
         }
-        Err(e) => {
+        Ok(Err(e)) => {
             error!("Error: {}", e);
-            Err(e.to_string())
+            Err(RunixError::Execution(e.to_string()))
         }
+        Err(_) => Err(RunixError::Timeout(EXECUTION_TIMEOUT)),
     }
 }
 
-pub async fn execute_code_detailed(
+pub async fn execute_code(
     code: &str,
     language_name: &str,
     input: &str,
-) -> Result<ExecutionResult, String> {
-    let client = piston_rs::Client::new();
-    let executor = piston_rs::Executor::new()
-        .set_language(language_name)
-        .set_version("*")
-        .set_stdin(input)
-        .add_file(
-            piston_rs::File::default()
-                .set_name("main.cpp")
-                .set_content(code),
-        );
-
-    match client.execute(&executor).await {
-        Ok(response) => {
-            debug!("Language: {}", response.language);
-            debug!("Version: {}", response.version);
-
-            if let Some(ref c) = response.compile {
-                debug!("Compilation: {}", c.output);
-            }
+) -> RunixResult<String> {
+    let response = run_piston(code, language_name, input).await?;
+    Ok(response.run.output)
+}
 
-            debug!("Output: {}", response.run.output);
-            Ok(ExecutionResult {
-                output: response.run.output,
-                compile_output: response.compile.map(|c| c.output),
-            })
-        }
-        Err(e) => {
-            error!("Error: {}", e);
-            Err(e.to_string())
-        }
-    }
+pub async fn execute_code_detailed(
+    code: &str,
+    language_name: &str,
+    input: &str,
+) -> RunixResult<ExecutionResult> {
+    let response = run_piston(code, language_name, input).await?;
+    let compile_output = response.compile.map(|c| c.output);
+    let mut diagnostics = compile_output.as_deref().map(parse_compiler_output).unwrap_or_default();
+    diagnostics.extend(parse_compiler_output(&response.run.stderr));
+    Ok(ExecutionResult {
+        output: response.run.output,
+        compile_output,
+        diagnostics,
+    })
 }
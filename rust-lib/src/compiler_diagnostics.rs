@@ -0,0 +1,373 @@
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::TextBuffer;
+use crate::offsets::{OffsetEncoding, TextPoint};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl DiagnosticSeverity {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "error" | "fatal error" => Some(DiagnosticSeverity::Error),
+            "warning" => Some(DiagnosticSeverity::Warning),
+            "note" => Some(DiagnosticSeverity::Note),
+            _ => None,
+        }
+    }
+}
+
+/// Diagnóstico de un compilador o intérprete. `line` y `column` son 1-based, tal como
+/// los imprime la herramienta; las notas asociadas van en `notes`.
+///
+/// `column` se trata como columna en bytes UTF-8 de la línea, que es lo que cuentan gcc y
+/// clang (la de javac se calcula así a partir del `^`). rustc y kotlinc cuentan caracteres,
+/// lo que sólo difiere en líneas con texto no ASCII.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompilerDiagnostic {
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    #[serde(default)]
+    pub notes: Vec<String>,
+    /// Offset en el documento abierto, en la codificación pedida; sólo lo rellena
+    /// `locate_diagnostics` y sólo si `file` es ese documento
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+impl CompilerDiagnostic {
+    fn new(severity: DiagnosticSeverity, message: &str) -> Self {
+        Self { file: None, line: None, column: None, severity, message: message.trim().to_string(), notes: Vec::new(), offset: None }
+    }
+}
+
+/// Extrae diagnósticos de la salida de g++/clang, javac, rustc, kotlinc o de un traceback
+/// de Python. Las líneas que no reconoce (código citado, resúmenes...) se ignoran.
+pub fn parse_compiler_output(output: &str) -> Vec<CompilerDiagnostic> {
+    let mut diagnostics: Vec<CompilerDiagnostic> = Vec::new();
+    // Índice del diagnóstico de rustc abierto, que espera su `-->` y sus notas
+    let mut rust_open: Option<usize> = None;
+    // Marcos (file, line, función) del traceback de Python en curso
+    let mut python_frames: Vec<(String, usize, Option<String>)> = Vec::new();
+    // Línea anterior, la línea de código que javac cita sobre el `^`
+    let mut previous_line = "";
+
+    for raw in output.lines() {
+        let quoted = std::mem::replace(&mut previous_line, raw.trim_end());
+        let line = raw.trim_end();
+        let trimmed = line.trim_start();
+
+        // Python: marcos `File "main.py", line 3, in f` y la excepción final sin sangría
+        if let Some(frame) = parse_python_frame(trimmed) {
+            python_frames.push(frame);
+            continue;
+        }
+        if line.starts_with("Traceback (most recent call last)") {
+            continue;
+        }
+        if !python_frames.is_empty() {
+            if line.starts_with(char::is_whitespace) || line.is_empty() {
+                continue;
+            }
+            let (file, line_number, _) = python_frames.pop().unwrap_or_default();
+            let mut diagnostic = CompilerDiagnostic::new(DiagnosticSeverity::Error, line);
+            diagnostic.file = Some(file);
+            diagnostic.line = Some(line_number);
+            diagnostic.notes = python_frames.drain(..).map(|(file, line, function)| match function {
+                Some(function) => format!("{}:{} in {}", file, line, function),
+                None => format!("{}:{}", file, line),
+            }).collect();
+            diagnostics.push(diagnostic);
+            continue;
+        }
+
+        // rustc: `error[E0425]: ...` seguido de ` --> src/main.rs:3:5` y notas `= note: ...`
+        if let Some((severity, message)) = parse_rust_header(line) {
+            if severity == DiagnosticSeverity::Note || is_rust_summary(&message) {
+                if let (Some(index), DiagnosticSeverity::Note) = (rust_open, severity) {
+                    diagnostics[index].notes.push(message);
+                }
+                continue;
+            }
+            diagnostics.push(CompilerDiagnostic::new(severity, &message));
+            rust_open = Some(diagnostics.len() - 1);
+            continue;
+        }
+        if let Some(index) = rust_open {
+            let diagnostic = &mut diagnostics[index];
+            if let Some(location) = trimmed.strip_prefix("--> ") {
+                if diagnostic.line.is_none() {
+                    set_location(diagnostic, location);
+                }
+                continue;
+            }
+            if let Some(note) = trimmed.strip_prefix("= ") {
+                diagnostic.notes.push(note.to_string());
+                continue;
+            }
+            if trimmed.is_empty() || trimmed.starts_with('|') || trimmed.split_once('|').is_some_and(|(n, _)| n.trim().chars().all(|c| c.is_ascii_digit())) {
+                continue;
+            }
+            rust_open = None;
+        }
+
+        // kotlinc: `e: file:///main.kt:3:5 mensaje` o `e: /main.kt: (3, 5): mensaje`
+        if let Some(diagnostic) = parse_kotlin_line(line) {
+            diagnostics.push(diagnostic);
+            continue;
+        }
+
+        // g++/clang/javac/kotlinc antiguo: `main.cpp:5:10: error: mensaje`
+        if let Some(diagnostic) = parse_gnu_line(line) {
+            match diagnostics.last_mut() {
+                Some(previous) if diagnostic.severity == DiagnosticSeverity::Note && previous.severity != DiagnosticSeverity::Note => {
+                    previous.notes.push(format_note(&diagnostic));
+                }
+                _ => diagnostics.push(diagnostic),
+            }
+            continue;
+        }
+
+        // javac: la columna sólo aparece como un `^` bajo la línea citada
+        if trimmed == "^" {
+            if let Some(previous) = diagnostics.last_mut().filter(|d| d.line.is_some() && d.column.is_none()) {
+                previous.column = Some(caret_column(quoted, line));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Sitúa en el documento abierto los diagnósticos cuyo `file` es `file_name`: la línea y la
+/// columna (en bytes) pasan a un offset en `encoding`. Los de otros archivos, o con una línea
+/// que el documento no tiene, quedan sin `offset`.
+pub fn locate_diagnostics(diagnostics: &mut [CompilerDiagnostic], buffer: &TextBuffer, file_name: &str, encoding: OffsetEncoding) {
+    for diagnostic in diagnostics {
+        let (Some(file), Some(line)) = (&diagnostic.file, diagnostic.line) else {
+            continue;
+        };
+        if !same_file(file, file_name) || line == 0 || line > buffer.line_count() {
+            continue;
+        }
+        let point = TextPoint { row: line - 1, column: diagnostic.column.unwrap_or(1).saturating_sub(1) };
+        diagnostic.offset = Some(buffer.convert(buffer.point_to_byte(point, OffsetEncoding::Utf8), encoding));
+    }
+}
+
+// Mismo archivo aunque sólo uno de los dos lleve directorio (`/tmp/run/main.cpp` y `main.cpp`)
+fn same_file(a: &str, b: &str) -> bool {
+    let (a, b) = (a.replace('\\', "/"), b.replace('\\', "/"));
+    a == b || a.ends_with(&format!("/{}", b)) || b.ends_with(&format!("/{}", a))
+}
+
+// Columna en bytes (1-based) de `quoted` bajo la que cae el `^` de `caret`. javac copia los
+// tabuladores de la línea citada, pero la salida puede llegar con ellos expandidos; midiendo
+// ambas líneas con tabuladores cada 8 columnas, como una terminal, valen los dos casos.
+fn caret_column(quoted: &str, caret: &str) -> usize {
+    let advance = |width: usize, c: char| if c == '\t' { (width / 8 + 1) * 8 } else { width + 1 };
+    let target = caret.chars().take_while(|&c| c != '^').fold(0, advance);
+    let mut width = 0;
+    for (index, c) in quoted.char_indices() {
+        let next = advance(width, c);
+        if next > target {
+            return index + 1;
+        }
+        width = next;
+    }
+    quoted.len() + 1
+}
+
+fn parse_python_frame(trimmed: &str) -> Option<(String, usize, Option<String>)> {
+    let rest = trimmed.strip_prefix("File \"")?;
+    let (file, rest) = rest.split_once('"')?;
+    let rest = rest.strip_prefix(", line ")?;
+    let (number, function) = match rest.split_once(", in ") {
+        Some((number, function)) => (number, Some(function.to_string())),
+        None => (rest, None),
+    };
+    Some((file.to_string(), number.trim().parse().ok()?, function))
+}
+
+fn parse_rust_header(line: &str) -> Option<(DiagnosticSeverity, String)> {
+    let (head, message) = line.split_once(": ")?;
+    let (name, code) = match head.split_once('[') {
+        Some((name, code)) => (name, Some(code.strip_suffix(']')?)),
+        None => (head, None),
+    };
+    let severity = match name {
+        "error" => DiagnosticSeverity::Error,
+        "warning" => DiagnosticSeverity::Warning,
+        "note" | "help" => DiagnosticSeverity::Note,
+        _ => return None,
+    };
+    let message = message.trim();
+    Some((severity, match code {
+        Some(code) => format!("[{}] {}", code, message),
+        None => message.to_string(),
+    }))
+}
+
+fn is_rust_summary(message: &str) -> bool {
+    message.starts_with("aborting due to")
+        || message.ends_with("warning emitted")
+        || message.ends_with("warnings emitted")
+}
+
+fn parse_kotlin_line(line: &str) -> Option<CompilerDiagnostic> {
+    let (tag, rest) = line.split_once(": ")?;
+    let severity = match tag {
+        "e" => DiagnosticSeverity::Error,
+        "w" => DiagnosticSeverity::Warning,
+        _ => return None,
+    };
+    let rest = rest.strip_prefix("file://").unwrap_or(rest);
+
+    let (location, message) = match rest.split_once(": (") {
+        Some((file, rest)) => {
+            let (position, message) = rest.split_once("): ")?;
+            let (row, column) = position.split_once(", ")?;
+            let mut diagnostic = CompilerDiagnostic::new(severity, message);
+            diagnostic.file = Some(file.to_string());
+            diagnostic.line = row.trim().parse().ok();
+            diagnostic.column = column.trim().parse().ok();
+            return Some(diagnostic);
+        }
+        None => rest.split_once(' ')?,
+    };
+    let mut diagnostic = CompilerDiagnostic::new(severity, message);
+    set_location(&mut diagnostic, location);
+    diagnostic.line?;
+    Some(diagnostic)
+}
+
+fn parse_gnu_line(line: &str) -> Option<CompilerDiagnostic> {
+    for marker in [": fatal error: ", ": error: ", ": warning: ", ": note: "] {
+        if let Some((location, message)) = line.split_once(marker) {
+            let severity = DiagnosticSeverity::parse(marker.trim_matches(|c| c == ':' || c == ' '))?;
+            let mut diagnostic = CompilerDiagnostic::new(severity, message);
+            set_location(&mut diagnostic, location);
+            // `collect2: error: ...` no tiene posición pero sigue siendo un error útil
+            if diagnostic.file.is_none() {
+                diagnostic.message = format!("{}: {}", location.trim(), diagnostic.message);
+            }
+            return Some(diagnostic);
+        }
+    }
+    None
+}
+
+// `file:line[:column]`, separando desde la derecha para admitir rutas con `:`
+fn set_location(diagnostic: &mut CompilerDiagnostic, location: &str) {
+    let location = location.trim().trim_end_matches(':');
+    let mut parts = location.rsplitn(3, ':');
+    let last = parts.next().and_then(|p| p.parse::<usize>().ok());
+    let middle = parts.next();
+    let (file, line, column) = match (last, middle.and_then(|p| p.parse::<usize>().ok()), parts.next()) {
+        (Some(column), Some(line), Some(file)) => (file, line, Some(column)),
+        (Some(line), _, _) => match location.rsplit_once(':') {
+            Some((file, _)) => (file, line, None),
+            None => return,
+        },
+        _ => return,
+    };
+    if file.is_empty() {
+        return;
+    }
+    diagnostic.file = Some(file.to_string());
+    diagnostic.line = Some(line);
+    diagnostic.column = column;
+}
+
+fn format_note(note: &CompilerDiagnostic) -> String {
+    match (&note.file, note.line, note.column) {
+        (Some(file), Some(line), Some(column)) => format!("{}:{}:{}: {}", file, line, column, note.message),
+        (Some(file), Some(line), None) => format!("{}:{}: {}", file, line, note.message),
+        _ => note.message.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locations(diagnostics: &[CompilerDiagnostic]) -> Vec<(Option<&str>, Option<usize>, Option<usize>)> {
+        diagnostics.iter().map(|d| (d.file.as_deref(), d.line, d.column)).collect()
+    }
+
+    #[test]
+    fn parses_gcc_javac_and_kotlinc() {
+        let gcc = "main.cpp: In function 'int main()':\n\
+            main.cpp:5:13: error: 'y' was not declared in this scope\n\
+            \x20   5 |     int x = y;\n\
+            \x20     |             ^\n\
+            main.cpp:2:6: note: suggested alternative: 'x'\n\
+            main.cpp:7:1: warning: no return statement [-Wreturn-type]\n";
+        let diagnostics = parse_compiler_output(gcc);
+        assert_eq!(locations(&diagnostics), vec![(Some("main.cpp"), Some(5), Some(13)), (Some("main.cpp"), Some(7), Some(1))]);
+        assert_eq!(diagnostics[0].notes, vec!["main.cpp:2:6: suggested alternative: 'x'"]);
+        assert_eq!(diagnostics[1].severity, DiagnosticSeverity::Warning);
+
+        let javac = "Main.java:3: error: ';' expected\n        int x = 1\n                 ^\n1 error\n";
+        let diagnostics = parse_compiler_output(javac);
+        assert_eq!(locations(&diagnostics), vec![(Some("Main.java"), Some(3), Some(18))]);
+        assert_eq!(diagnostics[0].message, "';' expected");
+
+        // Con tabuladores, copiados o expandidos en la línea del `^`
+        for caret in ["\t\t        ^".to_string(), format!("{}^", " ".repeat(24))] {
+            let javac = format!("Main.java:3: error: cannot find symbol\n\t\tint x = y;\n{}\n1 error\n", caret);
+            assert_eq!(parse_compiler_output(&javac)[0].column, Some(11));
+        }
+
+        let kotlin = "e: file:///code/main.kt:4:13 Unresolved reference 'foo'.\nw: /code/main.kt: (2, 9): Variable 'a' is never used\n";
+        let diagnostics = parse_compiler_output(kotlin);
+        assert_eq!(locations(&diagnostics), vec![(Some("/code/main.kt"), Some(4), Some(13)), (Some("/code/main.kt"), Some(2), Some(9))]);
+        assert_eq!(diagnostics[1].severity, DiagnosticSeverity::Warning);
+    }
+
+    #[test]
+    fn parses_rustc_and_python() {
+        let rustc = "error[E0425]: cannot find value `y` in this scope\n\
+            \x20--> src/main.rs:3:13\n\
+            \x20 |\n\
+            3 |     let x = y;\n\
+            \x20 |             ^ help: a local variable with a similar name exists: `x`\n\
+            \x20 = note: see issue #1\n\
+            \n\
+            error: aborting due to 1 previous error\n";
+        let diagnostics = parse_compiler_output(rustc);
+        assert_eq!(locations(&diagnostics), vec![(Some("src/main.rs"), Some(3), Some(13))]);
+        assert_eq!(diagnostics[0].message, "[E0425] cannot find value `y` in this scope");
+        assert_eq!(diagnostics[0].notes, vec!["note: see issue #1"]);
+
+        let python = "Traceback (most recent call last):\n\
+            \x20 File \"main.py\", line 5, in <module>\n\
+            \x20   main()\n\
+            \x20 File \"main.py\", line 2, in main\n\
+            \x20   print(x)\n\
+            NameError: name 'x' is not defined\n";
+        let diagnostics = parse_compiler_output(python);
+        assert_eq!(locations(&diagnostics), vec![(Some("main.py"), Some(2), None)]);
+        assert_eq!(diagnostics[0].message, "NameError: name 'x' is not defined");
+        assert_eq!(diagnostics[0].notes, vec!["main.py:5 in <module>"]);
+    }
+
+    #[test]
+    fn locates_diagnostics_in_the_open_document() {
+        let buffer = TextBuffer::new("// ñ\nint main() { return y; }\n");
+        let output = "/tmp/run/main.cpp:2:21: error: 'y' was not declared in this scope\n\
+            other.cpp:2:1: error: elsewhere\n\
+            main.cpp:9:1: warning: past the end\n";
+        let mut diagnostics = parse_compiler_output(output);
+        locate_diagnostics(&mut diagnostics, &buffer, "main.cpp", OffsetEncoding::Utf16);
+        let offsets: Vec<Option<usize>> = diagnostics.iter().map(|d| d.offset).collect();
+        assert_eq!(offsets, vec![Some(25), None, None]);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use log::warn;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Language, Node, Query, QueryCursor, StreamingIterator};
+
+use crate::buffer::TextBuffer;
+use crate::cache::LockExt;
+use crate::document::Document;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionKind {
+    /// Definición visible desde el cursor según `locals.scm`
+    Local,
+    /// Campo o método visto en el archivo, tras `.`, `->` o `::`
+    Member,
+    /// Cualquier otro identificador del archivo
+    Identifier,
+    Keyword,
+    /// Nombre de la librería estándar del diccionario del lenguaje
+    Library,
+}
+
+impl CompletionKind {
+    // Desempate entre candidatos con la misma puntuación difusa
+    fn priority(self) -> i32 {
+        match self {
+            CompletionKind::Local => 4,
+            CompletionKind::Member => 3,
+            CompletionKind::Identifier => 2,
+            CompletionKind::Keyword => 1,
+            CompletionKind::Library => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub score: i32,
+}
+
+/// Candidatos ordenados y el prefijo que reemplazan (`prefix_start..prefix_end`).
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionList {
+    pub prefix_start: usize,
+    pub prefix_end: usize,
+    pub items: Vec<CompletionItem>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Dictionary {
+    keywords: Vec<String>,
+    library: Vec<String>,
+}
+
+// Palabras clave y nombres de la librería estándar incluidos en la librería
+static DICTIONARIES: Lazy<HashMap<&'static str, Dictionary>> = Lazy::new(|| {
+    [
+        ("cpp", include_str!("../completions/cpp.json")),
+        ("javascript", include_str!("../completions/javascript.json")),
+        ("python", include_str!("../completions/python.json")),
+    ]
+    .into_iter()
+    .map(|(language, json)| (language, serde_json::from_str(json).unwrap_or_else(|e| {
+        warn!("Invalid completion dictionary for {}: {}", language, e);
+        Dictionary::default()
+    })))
+    .collect()
+});
+
+// Queries de locals compiladas, por lenguaje y hash del código fuente
+type LocalsQueryCache = HashMap<(String, u64), Option<Arc<Query>>>;
+
+static LOCALS_QUERIES: Lazy<Mutex<LocalsQueryCache>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// `; inherits: c` no lo resuelve el lado Kotlin; las bases de los lenguajes soportados van
+// incluidas aquí
+fn bundled_locals(language: &str) -> Option<&'static str> {
+    match language {
+        "c" => Some(include_str!("../queries/c/locals.scm")),
+        "ecma" => Some(include_str!("../queries/ecma/locals.scm")),
+        _ => None,
+    }
+}
+
+fn resolve_inherits(source: &str) -> String {
+    let Some(bases) = source.lines().next().and_then(|line| line.strip_prefix("; inherits:")) else {
+        return source.to_string();
+    };
+    let mut resolved = String::new();
+    for base in bases.split(',').filter_map(|base| bundled_locals(base.trim())) {
+        resolved.push_str(&resolve_inherits(base));
+        resolved.push('\n');
+    }
+    resolved.push_str(source);
+    resolved
+}
+
+fn locals_query(language_name: &str, language: &Language, locals_scm: &str) -> Option<Arc<Query>> {
+    if locals_scm.trim().is_empty() {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    locals_scm.hash(&mut hasher);
+    let key = (language_name.to_string(), hasher.finish());
+    if let Some(query) = LOCALS_QUERIES.lock_or_recover().get(&key) {
+        return query.clone();
+    }
+    // Una query inválida sólo desactiva las sugerencias por ámbito
+    let query = Query::new(language, &resolve_inherits(locals_scm))
+        .map_err(|e| warn!("Invalid locals query for {}: {}", language_name, e))
+        .ok()
+        .map(Arc::new);
+    LOCALS_QUERIES.lock_or_recover().insert(key, query.clone());
+    query
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Propone completados para el cursor `cursor` (en bytes), ordenados por puntuación.
+pub fn complete(document: &Document, cursor: usize, limit: usize) -> CompletionList {
+    let buffer = document.buffer();
+    let cursor = cursor.min(buffer.len());
+    // El prefijo y lo que lo precede no pasan de la línea del cursor
+    let line_start = buffer.line_start(buffer.point_at(cursor).row);
+    let line = buffer.slice(line_start..cursor);
+    let prefix_column = line.char_indices().rev()
+        .take_while(|&(_, c)| is_identifier_char(c))
+        .last()
+        .map_or(line.len(), |(i, _)| i);
+    let prefix_start = line_start + prefix_column;
+    let prefix = &line[prefix_column..];
+    let before = line[..prefix_column].trim_end_matches([' ', '\t']);
+    let member_access = before.ends_with('.') || before.ends_with("->");
+    let scope_access = before.ends_with("::");
+
+    let mut candidates: HashMap<String, (CompletionKind, Option<String>)> = HashMap::new();
+    let mut add = |label: &str, kind: CompletionKind, detail: Option<String>| {
+        if label.is_empty() || label == prefix {
+            return;
+        }
+        let entry = candidates.entry(label.to_string()).or_insert((kind, detail.clone()));
+        if kind.priority() > entry.0.priority() {
+            *entry = (kind, detail);
+        }
+    };
+
+    let root = document.tree.root_node();
+    if member_access {
+        for name in member_names(root, buffer, cursor) {
+            add(&name, CompletionKind::Member, None);
+        }
+    } else {
+        for (name, detail) in visible_locals(document, cursor) {
+            add(&name, CompletionKind::Local, Some(detail));
+        }
+        for name in file_identifiers(root, buffer, cursor) {
+            add(&name, CompletionKind::Identifier, None);
+        }
+        let dictionary = DICTIONARIES.get(document.language_name.as_str());
+        if let Some(dictionary) = dictionary {
+            if !scope_access {
+                for keyword in &dictionary.keywords {
+                    add(keyword, CompletionKind::Keyword, None);
+                }
+            }
+            for name in &dictionary.library {
+                add(name, CompletionKind::Library, None);
+            }
+        }
+    }
+
+    let mut items: Vec<CompletionItem> = candidates.into_iter()
+        .filter_map(|(label, (kind, detail))| {
+            let score = fuzzy_score(prefix, &label)? + kind.priority();
+            Some(CompletionItem { label, kind, detail, score })
+        })
+        .collect();
+    items.sort_by(|a, b| b.score.cmp(&a.score)
+        .then(a.label.len().cmp(&b.label.len()))
+        .then(a.label.cmp(&b.label)));
+    items.truncate(limit);
+
+    CompletionList { prefix_start, prefix_end: cursor, items }
+}
+
+// Definiciones de `locals.scm` cuyo ámbito contiene el cursor. Las variables sólo cuentan
+// si se declaran antes; funciones y tipos son visibles en todo su ámbito.
+fn visible_locals(document: &Document, cursor: usize) -> Vec<(String, String)> {
+    let Some(query) = locals_query(&document.language_name, &document.language, &document.locals_scm) else {
+        return Vec::new();
+    };
+    let buffer = document.buffer();
+
+    let mut scopes: Vec<Range<usize>> = Vec::new();
+    // (rango del nombre, tipo, si pertenece al ámbito padre)
+    let mut definitions: Vec<(Range<usize>, String, bool)> = Vec::new();
+    let mut query_cursor = QueryCursor::new();
+    let mut matches = query_cursor.matches(&query, document.tree.root_node(), |node: Node| buffer.chunks(node.byte_range()));
+    while let Some(query_match) = matches.next() {
+        let parent_scope = query.property_settings(query_match.pattern_index).iter()
+            .any(|p| p.key.ends_with(".scope") && p.value.as_deref() == Some("parent"));
+        for capture in query_match.captures {
+            let name = query.capture_names()[capture.index as usize];
+            if name == "local.scope" {
+                scopes.push(capture.node.byte_range());
+            } else if let Some(kind) = name.strip_prefix("local.definition") {
+                let kind = kind.trim_start_matches('.');
+                let kind = if kind.is_empty() { "definition" } else { kind };
+                definitions.push((capture.node.byte_range(), kind.to_string(), parent_scope));
+            }
+        }
+    }
+    scopes.sort_by_key(|scope| scope.end - scope.start);
+    scopes.dedup();
+
+    let mut visible = Vec::new();
+    for (range, kind, parent_scope) in definitions {
+        let mut enclosing = scopes.iter().filter(|s| s.start <= range.start && range.end <= s.end);
+        let scope = if parent_scope {
+            enclosing.nth(1)
+        } else {
+            enclosing.next()
+        };
+        let in_scope = scope.is_none_or(|s| s.start <= cursor && cursor <= s.end);
+        let hoisted = matches!(kind.as_str(), "function" | "method" | "type" | "namespace" | "macro");
+        if in_scope && (hoisted || range.end <= cursor) && range.end != cursor {
+            visible.push((buffer.slice(range), kind));
+        }
+    }
+    visible
+}
+
+// Campos y métodos que aparecen en el archivo (`obj.campo`, `p->x`, `self.attr`)
+fn member_names(root: Node, buffer: &TextBuffer, cursor: usize) -> HashSet<String> {
+    collect_nodes(root, buffer, cursor, |node| match node.kind() {
+        "field_identifier" | "property_identifier" => true,
+        "identifier" => node.parent().is_some_and(|p| p.kind() == "attribute")
+            && node.prev_sibling().is_some_and(|s| s.kind() == "."),
+        _ => false,
+    })
+}
+
+fn file_identifiers(root: Node, buffer: &TextBuffer, cursor: usize) -> HashSet<String> {
+    collect_nodes(root, buffer, cursor, |node| matches!(node.kind(), "identifier" | "type_identifier" | "namespace_identifier"))
+}
+
+// Texto de los nodos que cumplen `accept`, sin contar el que se está escribiendo
+fn collect_nodes(root: Node, buffer: &TextBuffer, cursor: usize, accept: impl Fn(&Node) -> bool) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if accept(&node) && node.end_byte() != cursor {
+            names.insert(buffer.slice(node.byte_range()));
+            continue;
+        }
+        let mut tree_cursor = node.walk();
+        stack.extend(node.children(&mut tree_cursor));
+    }
+    names
+}
+
+/// Puntuación de `pattern` como subsecuencia de `candidate` (sin distinguir mayúsculas).
+/// Premia el prefijo, las coincidencias consecutivas y los inicios de palabra
+/// (`_x`, `camelCase`); `None` si no es subsecuencia.
+pub fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i32> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for p in pattern.chars() {
+        let index = (position..chars.len()).find(|&i| chars[i].to_lowercase().eq(p.to_lowercase()))?;
+        let c = chars[index];
+        score += 1;
+        if c == p {
+            score += 1;
+        }
+        if index == 0 {
+            score += 6;
+        } else {
+            let before = chars[index - 1];
+            if !before.is_alphanumeric() || (before.is_lowercase() && c.is_uppercase()) {
+                score += 4;
+            }
+        }
+        match previous_match {
+            Some(previous) if previous + 1 == index => score += 5,
+            Some(previous) => score -= (index - previous - 1).min(3) as i32,
+            None => score -= index.min(3) as i32,
+        }
+        previous_match = Some(index);
+        position = index + 1;
+    }
+    // A igualdad de coincidencias, mejor los candidatos cortos
+    Some(score * 4 - (chars.len() - pattern.chars().count()).min(20) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_fuzzy_matches() {
+        assert!(fuzzy_score("lb", "lower_bound").unwrap() > fuzzy_score("lb", "label_builder_x").unwrap());
+        assert!(fuzzy_score("pb", "push_back").unwrap() > fuzzy_score("pb", "prob").unwrap());
+        assert_eq!(fuzzy_score("xyz", "lower_bound"), None);
+    }
+
+    #[test]
+    fn completes_locals_members_and_library() {
+        let code = "struct P { int cnt; };\nint total;\nint main() {\n  P p;\n  int count_x = 0;\n  co\n  p.\n}\nvoid later() { int hidden; }\n";
+        let document = Document::for_test("cpp", code, include_str!("../queries/cpp/locals.scm"));
+
+        let cursor = code.find("co\n").unwrap() + 2;
+        let list = complete(&document, cursor, 50);
+        assert_eq!(list.prefix_start, cursor - 2);
+        assert_eq!(list.items[0].label, "count_x");
+        assert_eq!(list.items[0].kind, CompletionKind::Local);
+        assert!(list.items.iter().any(|i| i.label == "const" && i.kind == CompletionKind::Keyword));
+        assert!(list.items.iter().any(|i| i.label == "count" && i.kind == CompletionKind::Library));
+        assert!(!list.items.iter().any(|i| i.label == "hidden" && i.kind == CompletionKind::Local));
+
+        let cursor = code.find("p.\n").unwrap() + 2;
+        let list = complete(&document, cursor, 50);
+        let labels: Vec<&str> = list.items.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(labels, vec!["cnt"]);
+    }
+}
@@ -0,0 +1,355 @@
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use once_cell::sync::Lazy;
+use tree_sitter::{Language, Parser, Tree};
+
+use crate::buffer::TextBuffer;
+use crate::cache::LockExt;
+use crate::error::{RunixError, RunixResult};
+use crate::highlighting::{load_language, LocalBindings, QueryHighlighter};
+use crate::history::{Change, EditKind, History};
+use crate::offsets::OffsetEncoding;
+use crate::selection::{expand_selection, shrink_selection};
+use crate::semantic_tokens::{diff_semantic_tokens, encode_semantic_tokens, SemanticTokens, SemanticTokensDelta, SemanticTokensLegend, SemanticTokensResult};
+use crate::types::{HighlightQueries, HighlightRange, TextEdit};
+
+/// Documento abierto en el lado nativo: conserva el texto y su árbol para que las
+/// consultas posteriores (viewport, diagnósticos...) no tengan que volver a parsear.
+///
+/// El texto vive en un rope que recibe las ediciones de Kotlin y alimenta al parser por
+/// tramos; la copia contigua que necesitan las queries se genera bajo demanda.
+pub struct Document {
+    pub language_name: String,
+    pub language: Language,
+    pub tree: Tree,
+    pub highlight_names: Arc<Vec<String>>,
+    pub highlighter: QueryHighlighter,
+    pub locals_scm: String,
+    pub version: u64,
+    parser: Parser,
+    buffer: TextBuffer,
+    // Texto contiguo de la versión actual; se invalida en cada edición
+    text: OnceCell<String>,
+    // Variables locales de la versión actual para el resaltado; se invalida al reparsear
+    locals: OnceCell<LocalBindings>,
+    // Últimos tokens entregados, base para semanticTokensDelta
+    last_semantic_tokens: Option<SemanticTokens>,
+    // Pares (anterior, ampliada) de expandSelection, para que shrink deshaga el camino exacto
+    selection_history: Vec<(Range<usize>, Range<usize>)>,
+    history: History,
+}
+
+impl Document {
+    pub fn new(
+        language_name: &str,
+        text: String,
+        queries: &HighlightQueries,
+        highlight_names: Arc<Vec<String>>,
+    ) -> RunixResult<Self> {
+        let language = load_language(language_name)?;
+        let mut parser = Parser::new();
+        parser.set_language(&language)
+            .map_err(|e| RunixError::UnsupportedLanguage(format!("{}: {}", language_name, e)))?;
+        let buffer = TextBuffer::new(&text);
+        let tree = parser.parse(&text, None)
+            .ok_or_else(|| RunixError::Parse(format!("parsing failed for language {}", language_name)))?;
+        let highlighter = QueryHighlighter::new(&language, queries.highlights, queries.locals, &highlight_names)?;
+
+        Ok(Self {
+            language_name: language_name.to_string(),
+            language,
+            tree,
+            highlight_names,
+            highlighter,
+            locals_scm: queries.locals.to_string(),
+            version: 0,
+            parser,
+            buffer,
+            text: OnceCell::from(text),
+            locals: OnceCell::new(),
+            last_semantic_tokens: None,
+            selection_history: Vec::new(),
+            history: History::default(),
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.get_or_init(|| self.buffer.to_string())
+    }
+
+    pub fn buffer(&self) -> &TextBuffer {
+        &self.buffer
+    }
+
+    /// Reemplaza el texto completo. El cambio se reduce al tramo entre el prefijo y el
+    /// sufijo comunes para que tree-sitter reparsee de forma incremental.
+    pub fn set_text(&mut self, new_text: String) -> RunixResult<()> {
+        let text = self.text();
+        if new_text == text {
+            return Ok(());
+        }
+
+        let old = text.as_bytes();
+        let new = new_text.as_bytes();
+        let mut prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        while !new_text.is_char_boundary(prefix) || !text.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old.iter().rev().zip(new.iter().rev()).take(max_suffix).take_while(|(a, b)| a == b).count();
+        while !new_text.is_char_boundary(new.len() - suffix) || !text.is_char_boundary(old.len() - suffix) {
+            suffix -= 1;
+        }
+
+        let old_end = old.len() - suffix;
+        let change = Change {
+            start: prefix,
+            removed: text[prefix..old_end].to_string(),
+            inserted: new_text[prefix..new.len() - suffix].to_string(),
+        };
+        let edit = self.buffer.edit(prefix..old_end, &change.inserted);
+        self.tree.edit(&edit);
+        self.history.record(EditKind::classify(std::slice::from_ref(&change)), vec![change], now_ms());
+        self.text = OnceCell::from(new_text);
+        self.selection_history.clear();
+        self.reparse()
+    }
+
+    /// Aplica edits no solapados (offsets en bytes del texto actual) y reparsea una sola vez.
+    /// Se registran en el historial con `kind` o, si es `None`, con el que se deduzca.
+    pub fn apply_edits(&mut self, edits: &[TextEdit], kind: Option<EditKind>) -> RunixResult<()> {
+        let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+        sorted.sort_by_key(|edit| (edit.start, edit.end));
+        for pair in sorted.windows(2) {
+            if pair[0].end > pair[1].start {
+                return Err(RunixError::InvalidArgument(format!(
+                    "overlapping edits at {}..{} and {}..{}", pair[0].start, pair[0].end, pair[1].start, pair[1].end
+                )));
+            }
+        }
+        if let Some(edit) = sorted.iter().find(|edit| edit.start > edit.end || edit.end > self.buffer.len()) {
+            return Err(RunixError::InvalidArgument(format!("edit {}..{} out of bounds", edit.start, edit.end)));
+        }
+        if sorted.is_empty() {
+            return Ok(());
+        }
+
+        let changes: Vec<Change> = sorted.iter()
+            .map(|edit| Change { start: edit.start, removed: self.buffer.slice(edit.start..edit.end), inserted: edit.text.clone() })
+            .collect();
+        // De atrás hacia delante para que los offsets de los anteriores sigan siendo válidos
+        for edit in sorted.iter().rev() {
+            let input_edit = self.buffer.edit(edit.start..edit.end, &edit.text);
+            self.tree.edit(&input_edit);
+        }
+        let kind = kind.unwrap_or_else(|| EditKind::classify(&changes));
+        self.history.record(kind, changes, now_ms());
+        self.text = OnceCell::new();
+        self.selection_history.clear();
+        self.reparse()
+    }
+
+    pub fn history(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    pub fn begin_undo_group(&mut self, kind: EditKind) {
+        self.history.begin_group(kind, now_ms());
+    }
+
+    /// Deshace la última transacción. Devuelve los edits aplicados en orden, cada uno sobre
+    /// el texto que deja el anterior, con offsets en `encoding`; vacío si no hay nada.
+    pub fn undo(&mut self, encoding: OffsetEncoding) -> RunixResult<Vec<TextEdit>> {
+        let edits = self.history.undo().unwrap_or_default();
+        self.apply_in_order(edits, encoding)
+    }
+
+    pub fn redo(&mut self, encoding: OffsetEncoding) -> RunixResult<Vec<TextEdit>> {
+        let edits = self.history.redo().unwrap_or_default();
+        self.apply_in_order(edits, encoding)
+    }
+
+    // Aplica edits secuenciales sin registrarlos; los offsets se convierten antes de cada
+    // edit, porque cada uno se refiere al texto intermedio
+    fn apply_in_order(&mut self, mut edits: Vec<TextEdit>, encoding: OffsetEncoding) -> RunixResult<Vec<TextEdit>> {
+        if edits.is_empty() {
+            return Ok(edits);
+        }
+        for edit in &mut edits {
+            let (start, end) = (self.buffer.convert(edit.start, encoding), self.buffer.convert(edit.end, encoding));
+            let input_edit = self.buffer.edit(edit.start..edit.end, &edit.text);
+            self.tree.edit(&input_edit);
+            (edit.start, edit.end) = (start, end);
+        }
+        self.text = OnceCell::new();
+        self.selection_history.clear();
+        self.reparse()?;
+        Ok(edits)
+    }
+
+    pub fn reparse(&mut self) -> RunixResult<()> {
+        let buffer = &self.buffer;
+        self.tree = self.parser.parse_with_options(&mut |byte, _| buffer.chunk_at(byte), Some(&self.tree), None)
+            .ok_or_else(|| RunixError::Parse(format!("parsing failed for language {}", self.language_name)))?;
+        self.locals = OnceCell::new();
+        self.version += 1;
+        Ok(())
+    }
+
+    pub fn highlight_range(&self, range: Range<usize>) -> Vec<HighlightRange> {
+        let locals = self.locals.get_or_init(|| self.highlighter.resolve_locals(&self.tree, &self.buffer));
+        self.highlighter.highlight_range(&self.tree, &self.buffer, range, locals)
+    }
+
+    /// Amplía la selección al siguiente nodo con nombre que la contiene.
+    pub fn expand_selection(&mut self, selection: Range<usize>) -> Range<usize> {
+        match expand_selection(&self.tree, selection.clone()) {
+            Some(expanded) => {
+                self.selection_history.push((selection, expanded.clone()));
+                expanded
+            }
+            None => selection,
+        }
+    }
+
+    /// Deshace la última ampliación si la selección no ha cambiado desde entonces; si no,
+    /// baja al primer nodo con nombre contenido en ella.
+    pub fn shrink_selection(&mut self, selection: Range<usize>) -> Range<usize> {
+        while let Some((previous, expanded)) = self.selection_history.pop() {
+            if expanded == selection {
+                return previous;
+            }
+        }
+        shrink_selection(&self.tree, selection.clone()).unwrap_or(selection)
+    }
+
+    /// Tokens semánticos LSP del documento completo; el `result_id` es la versión del documento.
+    pub fn semantic_tokens(&mut self) -> SemanticTokens {
+        let legend = SemanticTokensLegend::new(&self.highlight_names);
+        let ranges = self.highlight_range(0..self.buffer.len());
+        let tokens = SemanticTokens {
+            result_id: self.version.to_string(),
+            data: encode_semantic_tokens(&ranges, &self.buffer, &legend),
+        };
+        self.last_semantic_tokens = Some(tokens.clone());
+        tokens
+    }
+
+    /// Edits respecto a los tokens entregados con `previous_result_id`. Si esa versión ya no
+    /// está disponible se devuelven los tokens completos.
+    pub fn semantic_tokens_delta(&mut self, previous_result_id: &str) -> SemanticTokensResult {
+        let previous = self.last_semantic_tokens.take()
+            .filter(|tokens| tokens.result_id == previous_result_id);
+        let current = self.semantic_tokens();
+        match previous {
+            Some(previous) => SemanticTokensResult::Delta(SemanticTokensDelta {
+                result_id: current.result_id,
+                edits: diff_semantic_tokens(&previous.data, &current.data),
+            }),
+            None => SemanticTokensResult::Full(current),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Document {
+    /// Documento sin resaltado para los tests; `locals` puede ir vacío.
+    pub(crate) fn for_test(language_name: &str, code: &str, locals: &str) -> Self {
+        let queries = HighlightQueries { highlights: "", injections: "", locals };
+        Self::new(language_name, code.to_string(), &queries, Arc::new(Vec::new())).unwrap()
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+// Documentos abiertos indexados por el handle que recibe Kotlin
+static DOCUMENTS: Lazy<Mutex<HashMap<u64, Arc<Mutex<Document>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_DOCUMENT_ID: AtomicU64 = AtomicU64::new(1);
+
+pub fn open_document(document: Document) -> u64 {
+    let handle = NEXT_DOCUMENT_ID.fetch_add(1, Ordering::Relaxed);
+    DOCUMENTS.lock_or_recover().insert(handle, Arc::new(Mutex::new(document)));
+    handle
+}
+
+pub fn get_document(handle: u64) -> RunixResult<Arc<Mutex<Document>>> {
+    DOCUMENTS.lock_or_recover()
+        .get(&handle)
+        .cloned()
+        .ok_or_else(|| RunixError::InvalidArgument(format!("unknown document handle {}", handle)))
+}
+
+pub fn close_document(handle: u64) -> bool {
+    DOCUMENTS.lock_or_recover().remove(&handle).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use crate::highlighting::{highlight_code, DEFAULT_HIGHLIGHT_NAMES};
+
+    #[test]
+    fn open_document_highlights_faster_than_highlight_code_per_edit() {
+        // Lo que hacían highlightPacked/highlightIntoBuffer en cada llamada (resaltar el texto
+        // completo con las queries recibidas) frente al documento abierto tras cada edición
+        let queries = HighlightQueries {
+            highlights: include_str!("../queries/c/highlights.scm"),
+            injections: "",
+            locals: include_str!("../queries/c/locals.scm"),
+        };
+        let names: Vec<String> = DEFAULT_HIGHLIGHT_NAMES.iter().map(|s| s.to_string()).collect();
+        let mut text: String = (0..100).map(|i| format!("int f{i}(int x) {{ return x * {i}; }}\n")).collect();
+        let mut document = Document::new("cpp", text.clone(), &queries, Arc::new(names.clone())).unwrap();
+
+        let edits = 5;
+        let started = Instant::now();
+        for i in 0..edits {
+            text.insert(i, ' ');
+            highlight_code(&text, "cpp", &queries, &names).unwrap();
+        }
+        let full = started.elapsed();
+        let started = Instant::now();
+        for i in 0..edits {
+            document.apply_edits(&[TextEdit::new(i, i, " ")], None).unwrap();
+            assert!(!document.highlight_range(0..document.buffer().len()).is_empty());
+        }
+        let incremental = started.elapsed();
+        assert_eq!(document.text(), text);
+        assert!(incremental * 2 < full, "document {:?} vs highlight_code {:?}", incremental, full);
+    }
+
+    #[test]
+    fn applies_edits_with_a_single_incremental_reparse() {
+        let mut document = Document::for_test("cpp", "int a = 1;\nint b = 2;\n", "");
+
+        document.apply_edits(&[TextEdit::new(4, 5, "alpha"), TextEdit::new(15, 16, "beta")], None).unwrap();
+        assert_eq!(document.text(), "int alpha = 1;\nint beta = 2;\n");
+        assert_eq!(document.version, 1);
+        let mut parser = Parser::new();
+        parser.set_language(&document.language).unwrap();
+        let fresh = parser.parse(document.text(), None).unwrap();
+        assert_eq!(document.tree.root_node().to_sexp(), fresh.root_node().to_sexp());
+
+        assert!(document.apply_edits(&[TextEdit::new(0, 5, ""), TextEdit::new(3, 4, "")], None).is_err());
+        document.set_text("int c;\n".to_string()).unwrap();
+        assert_eq!(document.buffer().to_string(), "int c;\n");
+
+        let undone = document.undo(OffsetEncoding::Utf8).unwrap();
+        assert_eq!(undone, vec![TextEdit::new(4, 5, "alpha = 1;\nint beta = 2")]);
+        assert_eq!(document.text(), "int alpha = 1;\nint beta = 2;\n");
+        document.undo(OffsetEncoding::Utf8).unwrap();
+        assert_eq!(document.text(), "int a = 1;\nint b = 2;\n");
+        assert!(document.undo(OffsetEncoding::Utf8).unwrap().is_empty());
+        document.redo(OffsetEncoding::Utf8).unwrap();
+        assert_eq!(document.text(), "int alpha = 1;\nint beta = 2;\n");
+    }
+}
@@ -0,0 +1,58 @@
+use crate::types::HighlightRange;
+
+// Enteros por rango en la salida empaquetada: (start, end, highlight_type)
+pub const RANGE_STRIDE: usize = 3;
+pub const RANGE_BYTES: usize = RANGE_STRIDE * std::mem::size_of::<i32>();
+
+/// Empaqueta los rangos como tripletas `start, end, type` contiguas para un `int[]` de Java.
+pub fn pack_ranges(ranges: &[HighlightRange]) -> Vec<i32> {
+    let mut packed = Vec::with_capacity(ranges.len() * RANGE_STRIDE);
+    for range in ranges {
+        packed.push(range.start as i32);
+        packed.push(range.end as i32);
+        packed.push(range.highlight_type as i32);
+    }
+    packed
+}
+
+/// Escribe los rangos en `buf` como tripletas de `i32` en orden nativo, el mismo que usa
+/// un `ByteBuffer` directo con `order(ByteOrder.nativeOrder())`.
+/// Devuelve cuántos rangos se escribieron, o `None` si `buf` no tiene espacio para todos.
+pub fn write_ranges(ranges: &[HighlightRange], buf: &mut [u8]) -> Option<usize> {
+    if buf.len() < ranges.len() * RANGE_BYTES {
+        return None;
+    }
+    for (range, chunk) in ranges.iter().zip(buf.chunks_exact_mut(RANGE_BYTES)) {
+        chunk[0..4].copy_from_slice(&(range.start as i32).to_ne_bytes());
+        chunk[4..8].copy_from_slice(&(range.end as i32).to_ne_bytes());
+        chunk[8..12].copy_from_slice(&(range.highlight_type as i32).to_ne_bytes());
+    }
+    Some(ranges.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<HighlightRange> {
+        vec![
+            HighlightRange::new(0, 3, 2),
+            HighlightRange::new(4, 8, 1),
+        ]
+    }
+
+    #[test]
+    fn pack_ranges_as_triples() {
+        assert_eq!(pack_ranges(&sample()), vec![0, 3, 2, 4, 8, 1]);
+    }
+
+    #[test]
+    fn write_ranges_rejects_small_buffer() {
+        let mut small = vec![0u8; RANGE_BYTES];
+        assert_eq!(write_ranges(&sample(), &mut small), None);
+
+        let mut buf = vec![0u8; 2 * RANGE_BYTES];
+        assert_eq!(write_ranges(&sample(), &mut buf), Some(2));
+        assert_eq!(i32::from_ne_bytes(buf[12..16].try_into().unwrap()), 4);
+    }
+}
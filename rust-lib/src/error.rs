@@ -0,0 +1,181 @@
+use std::any::Any;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+use jni::JNIEnv;
+use jni::sys::{jboolean, jint, jlong, jobject};
+use log::error;
+
+/// Error común a todas las operaciones expuestas por JNI.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunixError {
+    /// tree-sitter no pudo producir un árbol para la entrada
+    Parse(String),
+    /// No hay gramática registrada para el lenguaje pedido
+    UnsupportedLanguage(String),
+    /// Una consulta `.scm` no compila
+    InvalidQuery(String),
+    /// Piston devolvió un error o no se pudo contactar
+    Execution(String),
+    /// La ejecución superó el tiempo máximo permitido
+    Timeout(Duration),
+    /// Un argumento recibido desde Java no es válido (UTF-16 mal formado, JSON inválido...)
+    InvalidArgument(String),
+    /// Fallo interno de la capa JNI
+    Jni(String),
+}
+
+pub type RunixResult<T> = Result<T, RunixError>;
+
+impl RunixError {
+    /// Todas las clases que puede devolver `java_class`, para resolverlas en `JNI_OnLoad`.
+    pub const JAVA_CLASSES: [&'static str; 7] = [
+        "com/example/lancelot/rust/ParseException",
+        "com/example/lancelot/rust/UnsupportedLanguageException",
+        "com/example/lancelot/rust/InvalidQueryException",
+        "com/example/lancelot/rust/ExecutionException",
+        "com/example/lancelot/rust/ExecutionTimeoutException",
+        "java/lang/IllegalArgumentException",
+        "com/example/lancelot/rust/RustException",
+    ];
+
+    /// Clase Java que se lanza para este error.
+    pub fn java_class(&self) -> &'static str {
+        match self {
+            RunixError::Parse(_) => "com/example/lancelot/rust/ParseException",
+            RunixError::UnsupportedLanguage(_) => "com/example/lancelot/rust/UnsupportedLanguageException",
+            RunixError::InvalidQuery(_) => "com/example/lancelot/rust/InvalidQueryException",
+            RunixError::Execution(_) => "com/example/lancelot/rust/ExecutionException",
+            RunixError::Timeout(_) => "com/example/lancelot/rust/ExecutionTimeoutException",
+            RunixError::InvalidArgument(_) => "java/lang/IllegalArgumentException",
+            RunixError::Jni(_) => "com/example/lancelot/rust/RustException",
+        }
+    }
+}
+
+impl fmt::Display for RunixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunixError::Parse(msg) => write!(f, "Parse error: {}", msg),
+            RunixError::UnsupportedLanguage(lang) => write!(f, "Unsupported language: {}", lang),
+            RunixError::InvalidQuery(msg) => write!(f, "Invalid query: {}", msg),
+            RunixError::Execution(msg) => write!(f, "Execution failed: {}", msg),
+            RunixError::Timeout(limit) => write!(f, "Execution timed out after {}s", limit.as_secs()),
+            RunixError::InvalidArgument(msg) => write!(f, "Invalid argument: {}", msg),
+            RunixError::Jni(msg) => write!(f, "JNI error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RunixError {}
+
+impl From<jni::errors::Error> for RunixError {
+    fn from(e: jni::errors::Error) -> Self {
+        RunixError::Jni(e.to_string())
+    }
+}
+
+impl From<tree_sitter::QueryError> for RunixError {
+    fn from(e: tree_sitter::QueryError) -> Self {
+        RunixError::InvalidQuery(format!("{} at row {}, column {}: {}", kind_name(&e.kind), e.row + 1, e.column + 1, e.message))
+    }
+}
+
+impl From<tree_sitter_highlight::Error> for RunixError {
+    fn from(e: tree_sitter_highlight::Error) -> Self {
+        match e {
+            tree_sitter_highlight::Error::InvalidLanguage => RunixError::UnsupportedLanguage("invalid language".to_string()),
+            other => RunixError::Parse(format!("{:?}", other)),
+        }
+    }
+}
+
+pub fn kind_name(kind: &tree_sitter::QueryErrorKind) -> &'static str {
+    match kind {
+        tree_sitter::QueryErrorKind::Syntax => "syntax",
+        tree_sitter::QueryErrorKind::NodeType => "node_type",
+        tree_sitter::QueryErrorKind::Field => "field",
+        tree_sitter::QueryErrorKind::Capture => "capture",
+        tree_sitter::QueryErrorKind::Predicate => "predicate",
+        tree_sitter::QueryErrorKind::Structure => "structure",
+        tree_sitter::QueryErrorKind::Language => "language",
+    }
+}
+
+/// Lanza la excepción Java correspondiente a `err`. Si ya hay una excepción pendiente
+/// se respeta la original.
+pub fn throw_error(env: &mut JNIEnv, err: &RunixError) {
+    error!("{}", err);
+    if env.exception_check().unwrap_or(false) {
+        return;
+    }
+    if env.throw_new(err.java_class(), err.to_string()).is_err() {
+        // La clase puede no existir (p. ej. ProGuard); recurrimos a RuntimeException
+        let _ = env.exception_clear();
+        let _ = env.throw_new("java/lang/RuntimeException", err.to_string());
+    }
+}
+
+/// Valor devuelto a Java cuando una función exportada no puede producir resultado.
+pub trait JniDefault {
+    fn jni_default() -> Self;
+}
+
+impl JniDefault for jobject {
+    fn jni_default() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+impl JniDefault for jlong {
+    fn jni_default() -> Self {
+        0
+    }
+}
+
+impl JniDefault for jint {
+    fn jni_default() -> Self {
+        0
+    }
+}
+
+impl JniDefault for jboolean {
+    fn jni_default() -> Self {
+        0
+    }
+}
+
+impl JniDefault for () {
+    fn jni_default() -> Self {}
+}
+
+/// Ejecuta el cuerpo de una función exportada atrapando cualquier pánico, para que
+/// nunca se desenrolle a través de `extern "system"`. El pánico se convierte en un
+/// `RuntimeException` con su mensaje.
+pub fn jni_guard<'local, R, F>(env: &mut JNIEnv<'local>, body: F) -> R
+where
+    R: JniDefault,
+    F: FnOnce(&mut JNIEnv<'local>) -> R,
+{
+    match catch_unwind(AssertUnwindSafe(|| body(env))) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = panic_message(&*payload);
+            error!("Panic caught at JNI boundary: {}", message);
+            if !env.exception_check().unwrap_or(false) {
+                let _ = env.throw_new("java/lang/RuntimeException", format!("Rust panic: {}", message));
+            }
+            R::jni_default()
+        }
+    }
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
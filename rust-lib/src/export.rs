@@ -0,0 +1,381 @@
+use std::fmt::Write;
+use serde::Deserialize;
+
+use crate::theme::{parse_color, ResolvedStyle, Theme};
+use crate::types::HighlightRange;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    Ansi,
+    Rtf,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "html" => Some(ExportFormat::Html),
+            "ansi" | "terminal" => Some(ExportFormat::Ansi),
+            "rtf" => Some(ExportFormat::Rtf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    // HTML: clases `hl-<captura>` con una hoja de estilos en vez de estilos en línea
+    pub css_classes: bool,
+    // HTML: documento completo con <head>; si no, solo el bloque <pre>
+    pub standalone: bool,
+    pub title: Option<String>,
+    pub font_family: String,
+    // RTF: tamaño en puntos
+    pub font_size: u32,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            css_classes: false,
+            standalone: true,
+            title: None,
+            font_family: "monospace".to_string(),
+            font_size: 10,
+        }
+    }
+}
+
+/// Renderiza `text` con los rangos resaltados (ordenados y sin solaparse, como los de
+/// `QueryHighlighter`) y los colores de `theme`.
+pub fn export_highlighted(
+    text: &str,
+    ranges: &[HighlightRange],
+    highlight_names: &[String],
+    theme: &Theme,
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> String {
+    let table = theme.resolve_styles(highlight_names);
+    let spans = split_spans(text, ranges);
+    let style_of = |highlight: Option<usize>| highlight.and_then(|h| table.style_for_highlight(h)).copied();
+
+    match format {
+        ExportFormat::Html => to_html(text, &spans, highlight_names, theme, options, &style_of),
+        ExportFormat::Ansi => to_ansi(text, &spans, &style_of),
+        ExportFormat::Rtf => to_rtf(text, &spans, theme, options, &style_of),
+    }
+}
+
+// Tramos consecutivos que cubren todo el texto; `None` es texto sin captura
+fn split_spans(text: &str, ranges: &[HighlightRange]) -> Vec<(usize, usize, Option<usize>)> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for range in ranges {
+        let start = range.start.clamp(pos, text.len());
+        let end = range.end.min(text.len());
+        if start >= end {
+            continue;
+        }
+        if pos < start {
+            spans.push((pos, start, None));
+        }
+        spans.push((start, end, Some(range.highlight_type)));
+        pos = end;
+    }
+    if pos < text.len() {
+        spans.push((pos, text.len(), None));
+    }
+    spans
+}
+
+fn to_html(
+    text: &str,
+    spans: &[(usize, usize, Option<usize>)],
+    highlight_names: &[String],
+    theme: &Theme,
+    options: &ExportOptions,
+    style_of: &dyn Fn(Option<usize>) -> Option<ResolvedStyle>,
+) -> String {
+    let base = base_css(theme, options);
+    let mut out = String::with_capacity(text.len() * 2);
+
+    if options.standalone {
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        if let Some(title) = &options.title {
+            let _ = writeln!(out, "<title>{}</title>", escape_html(title));
+        }
+    }
+    if options.css_classes {
+        out.push_str("<style>\n");
+        let _ = writeln!(out, "pre.runix {{ {} }}", base);
+        for (highlight_type, name) in highlight_names.iter().enumerate() {
+            if let Some(style) = style_of(Some(highlight_type)) {
+                let _ = writeln!(out, "pre.runix .{} {{ {} }}", css_class(name), style_css(&style));
+            }
+        }
+        out.push_str("</style>\n");
+    }
+    if options.standalone {
+        out.push_str("</head>\n<body>\n");
+    }
+
+    if options.css_classes {
+        out.push_str("<pre class=\"runix\">");
+    } else {
+        let _ = write!(out, "<pre style=\"{}\">", base);
+    }
+    for &(start, end, highlight) in spans {
+        let chunk = escape_html(&text[start..end]);
+        match highlight.zip(style_of(highlight)) {
+            Some((highlight_type, _)) if options.css_classes => {
+                let _ = write!(out, "<span class=\"{}\">{}</span>", css_class(&highlight_names[highlight_type]), chunk);
+            }
+            Some((_, style)) => {
+                let _ = write!(out, "<span style=\"{}\">{}</span>", style_css(&style), chunk);
+            }
+            None => out.push_str(&chunk),
+        }
+    }
+    out.push_str("</pre>\n");
+
+    if options.standalone {
+        out.push_str("</body>\n</html>\n");
+    }
+    out
+}
+
+fn base_css(theme: &Theme, options: &ExportOptions) -> String {
+    let mut css = format!("font-family: {};", css_font_family(&options.font_family));
+    if let Some(color) = theme.foreground.as_deref().and_then(parse_color) {
+        let _ = write!(css, " color: {};", css_color(color));
+    }
+    if let Some(color) = theme.background.as_deref().and_then(parse_color) {
+        let _ = write!(css, " background-color: {};", css_color(color));
+    }
+    css
+}
+
+// Deja sólo caracteres válidos en una lista de familias (`'Fira Code', monospace`) para que
+// el valor no pueda cerrar la regla ni el `<style>` en el que se escribe
+fn css_font_family(family: &str) -> String {
+    let family: String = family.chars()
+        .map(|c| if c == '"' { '\'' } else { c })
+        .filter(|&c| c.is_alphanumeric() || " -_,.'".contains(c))
+        .collect();
+    if family.trim().is_empty() { "monospace".to_string() } else { family }
+}
+
+fn style_css(style: &ResolvedStyle) -> String {
+    let mut css = Vec::new();
+    if let Some(color) = style.foreground {
+        css.push(format!("color: {}", css_color(color)));
+    }
+    if let Some(color) = style.background {
+        css.push(format!("background-color: {}", css_color(color)));
+    }
+    if style.bold { css.push("font-weight: bold".to_string()); }
+    if style.italic { css.push("font-style: italic".to_string()); }
+    if style.underline { css.push("text-decoration: underline".to_string()); }
+    css.join("; ")
+}
+
+// `function.builtin` → `hl-function-builtin`
+fn css_class(name: &str) -> String {
+    format!("hl-{}", name.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "-"))
+}
+
+fn css_color(argb: u32) -> String {
+    let (a, r, g, b) = argb_components(argb);
+    if a == 0xFF {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("rgba({}, {}, {}, {:.3})", r, g, b, a as f32 / 255.0)
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// Colores de 24 bits; cada línea se cierra con reset para que el estilo no se extienda
+// al prompt si la salida se corta
+fn to_ansi(
+    text: &str,
+    spans: &[(usize, usize, Option<usize>)],
+    style_of: &dyn Fn(Option<usize>) -> Option<ResolvedStyle>,
+) -> String {
+    const RESET: &str = "\x1b[0m";
+    let mut out = String::with_capacity(text.len() * 2);
+    for &(start, end, highlight) in spans {
+        let Some(style) = style_of(highlight) else {
+            out.push_str(&text[start..end]);
+            continue;
+        };
+        let mut codes = Vec::new();
+        if style.bold { codes.push("1".to_string()); }
+        if style.italic { codes.push("3".to_string()); }
+        if style.underline { codes.push("4".to_string()); }
+        if let Some(color) = style.foreground {
+            let (_, r, g, b) = argb_components(color);
+            codes.push(format!("38;2;{};{};{}", r, g, b));
+        }
+        if let Some(color) = style.background {
+            let (_, r, g, b) = argb_components(color);
+            codes.push(format!("48;2;{};{};{}", r, g, b));
+        }
+        let open = format!("\x1b[{}m", codes.join(";"));
+
+        for (i, line) in text[start..end].split('\n').enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            if !line.is_empty() {
+                out.push_str(&open);
+                out.push_str(line);
+                out.push_str(RESET);
+            }
+        }
+    }
+    out
+}
+
+fn to_rtf(
+    text: &str,
+    spans: &[(usize, usize, Option<usize>)],
+    theme: &Theme,
+    options: &ExportOptions,
+    style_of: &dyn Fn(Option<usize>) -> Option<ResolvedStyle>,
+) -> String {
+    // Tabla de colores de RTF: el índice 0 es el color por defecto
+    let mut colors: Vec<u32> = Vec::new();
+    let mut color_index = |argb: u32| -> usize {
+        let rgb = argb & 0x00FF_FFFF;
+        match colors.iter().position(|&c| c == rgb) {
+            Some(i) => i + 1,
+            None => {
+                colors.push(rgb);
+                colors.len()
+            }
+        }
+    };
+
+    let base_fg = theme.foreground.as_deref().and_then(parse_color).map(&mut color_index);
+    let base_bg = theme.background.as_deref().and_then(parse_color).map(&mut color_index);
+
+    let mut body = String::with_capacity(text.len() * 2);
+    for &(start, end, highlight) in spans {
+        let chunk = escape_rtf(&text[start..end]);
+        match style_of(highlight) {
+            Some(style) => {
+                body.push('{');
+                if let Some(color) = style.foreground {
+                    let _ = write!(body, "\\cf{}", color_index(color));
+                }
+                if let Some(color) = style.background {
+                    let _ = write!(body, "\\highlight{}", color_index(color));
+                }
+                if style.bold { body.push_str("\\b"); }
+                if style.italic { body.push_str("\\i"); }
+                if style.underline { body.push_str("\\ul"); }
+                let _ = write!(body, " {}}}", chunk);
+            }
+            None => body.push_str(&chunk),
+        }
+    }
+
+    let mut out = String::with_capacity(body.len() + 256);
+    let _ = write!(out, "{{\\rtf1\\ansi\\deff0{{\\fonttbl{{\\f0\\fmodern {};}}}}", escape_rtf(&options.font_family));
+    out.push_str("{\\colortbl;");
+    for rgb in &colors {
+        let (_, r, g, b) = argb_components(*rgb);
+        let _ = write!(out, "\\red{}\\green{}\\blue{};", r, g, b);
+    }
+    out.push_str("}\n");
+    let _ = write!(out, "\\f0\\fs{}", options.font_size * 2);
+    if let Some(index) = base_fg {
+        let _ = write!(out, "\\cf{}", index);
+    }
+    if let Some(index) = base_bg {
+        let _ = write!(out, "\\highlight{}", index);
+    }
+    out.push(' ');
+    out.push_str(&body);
+    out.push_str("}\n");
+    out
+}
+
+// RTF solo admite ASCII: el resto va como `\uN?` con unidades UTF-16 con signo
+fn escape_rtf(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\line\n"),
+            '\r' => {}
+            '\t' => out.push_str("\\tab "),
+            c if c.is_ascii() => out.push(c),
+            c => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    let _ = write!(out, "\\u{}?", *unit as i16);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn argb_components(argb: u32) -> (u8, u8, u8, u8) {
+    ((argb >> 24) as u8, (argb >> 16) as u8, (argb >> 8) as u8, argb as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_html_ansi_and_rtf() {
+        let text = "int x; // <é>";
+        let names = vec!["keyword".to_string(), "comment".to_string()];
+        let ranges = vec![HighlightRange::new(0, 3, 0), HighlightRange::new(7, text.len(), 1)];
+        let theme = Theme::from_json(r##"{"keyword": {"fg": "#C678DD", "bold": true}, "comment": "#5C6370"}"##).unwrap();
+
+        let options = ExportOptions { standalone: false, ..ExportOptions::default() };
+        let html = export_highlighted(text, &ranges, &names, &theme, ExportFormat::Html, &options);
+        assert!(html.contains("<span style=\"color: #c678dd; font-weight: bold\">int</span> x; "));
+        assert!(html.contains("// &lt;é&gt;</span>"));
+
+        let classes = ExportOptions { css_classes: true, ..options.clone() };
+        let html = export_highlighted(text, &ranges, &names, &theme, ExportFormat::Html, &classes);
+        assert!(html.contains("pre.runix .hl-comment { color: #5c6370 }"));
+        assert!(html.contains("<span class=\"hl-keyword\">int</span>"));
+
+        let hostile = ExportOptions { font_family: "\"Fira Code\"; } </style><script>x{}".to_string(), ..classes.clone() };
+        let html = export_highlighted(text, &ranges, &names, &theme, ExportFormat::Html, &hostile);
+        assert!(html.contains("pre.runix { font-family: 'Fira Code'  stylescriptx;"));
+        assert!(!html.contains("<script>"));
+
+        let ansi = export_highlighted(text, &ranges, &names, &theme, ExportFormat::Ansi, &options);
+        assert!(ansi.starts_with("\x1b[1;38;2;198;120;221mint\x1b[0m x; "));
+
+        let rtf = export_highlighted(text, &ranges, &names, &theme, ExportFormat::Rtf, &options);
+        assert!(rtf.contains("{\\colortbl;\\red198\\green120\\blue221;\\red92\\green99\\blue112;}"));
+        assert!(rtf.contains("{\\cf1\\b int} x; {\\cf2 // <\\u233?>}"));
+    }
+}
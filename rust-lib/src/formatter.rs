@@ -0,0 +1,241 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+use crate::text_utils::minimal_edits;
+use crate::types::TextEdit;
+
+/// Opciones de `format`. Con `external` se prueba antes clang-format, black o prettier; si
+/// no están instalados o fallan, se reindenta con el árbol de sintaxis.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FormatOptions {
+    pub indent_width: usize,
+    pub use_tabs: bool,
+    pub external: bool,
+    pub insert_final_newline: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent_width: 4, use_tabs: false, external: true, insert_final_newline: true }
+    }
+}
+
+// Tiempo máximo del formateador externo antes de matarlo y reindentar con el árbol
+const EXTERNAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Edits mínimos sobre el texto del documento y el formateador que los ha producido.
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatResult {
+    pub edits: Vec<TextEdit>,
+    pub formatter: String,
+}
+
+/// Formatea `text`, cuyo árbol es `tree`. No recibe el `Document` para que el llamador
+/// pueda soltar su bloqueo mientras corre el formateador externo.
+pub fn format_document(language: &str, text: &str, tree: &Tree, options: &FormatOptions) -> FormatResult {
+    let external = options.external
+        .then(|| run_external(language, text, options))
+        .flatten();
+    let (formatted, formatter) = match external {
+        Some(result) => result,
+        None => (reindent(language, text, tree, options), "tree-sitter".to_string()),
+    };
+    FormatResult { edits: minimal_edits(text, &formatted), formatter }
+}
+
+// Pasa el texto por stdin al formateador del lenguaje; None si no está, devuelve error o
+// supera `EXTERNAL_TIMEOUT`
+fn run_external(language: &str, text: &str, options: &FormatOptions) -> Option<(String, String)> {
+    let indent = options.indent_width.to_string();
+    let (program, args): (&str, Vec<String>) = match language {
+        "cpp" | "c" => ("clang-format", vec![
+            "--assume-filename=main.cpp".into(),
+            format!("--style={{BasedOnStyle: LLVM, IndentWidth: {}, UseTab: {}}}", indent, if options.use_tabs { "Always" } else { "Never" }),
+        ]),
+        "python" => ("black", vec!["--quiet".into(), "-".into()]),
+        "javascript" => {
+            let mut args = vec!["--stdin-filepath".into(), "main.js".into(), "--tab-width".into(), indent];
+            if options.use_tabs {
+                args.push("--use-tabs".into());
+            }
+            ("prettier", args)
+        }
+        _ => return None,
+    };
+
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| debug!("{} not available: {}", program, e))
+        .ok()?;
+    // stdin, stdout y stderr van en hilos propios para que ninguna tubería llena bloquee al
+    // proceso mientras esperamos
+    let mut stdin = child.stdin.take()?;
+    let input = text.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let stdout = read_pipe(child.stdout.take()?);
+    let stderr = read_pipe(child.stderr.take()?);
+
+    let deadline = Instant::now() + EXTERNAL_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            result => {
+                debug!("{} did not finish in {:?}: {:?}", program, EXTERNAL_TIMEOUT, result.err());
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+    writer.join().ok()?.ok()?;
+    let (stdout, stderr) = (stdout.join().ok()?, stderr.join().ok()?);
+
+    if !status.success() {
+        debug!("{} failed: {}", program, String::from_utf8_lossy(&stderr));
+        return None;
+    }
+    Some((String::from_utf8(stdout).ok()?, program.to_string()))
+}
+
+fn read_pipe(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+// Nodos cuyo contenido va un nivel más adentro que su primera línea
+fn indents(language: &str, kind: &str) -> bool {
+    match language {
+        "cpp" | "c" => matches!(kind,
+            "compound_statement" | "field_declaration_list" | "enumerator_list" | "initializer_list"
+            | "declaration_list" | "case_statement" | "argument_list" | "parameter_list"),
+        "javascript" => matches!(kind,
+            "statement_block" | "class_body" | "object" | "array" | "switch_body" | "switch_case"
+            | "switch_default" | "arguments" | "formal_parameters" | "object_pattern" | "named_imports"),
+        "python" => matches!(kind,
+            "block" | "argument_list" | "parameters" | "list" | "dictionary" | "set" | "tuple"
+            | "parenthesized_expression" | "list_comprehension" | "dictionary_comprehension"
+            | "set_comprehension" | "generator_expression"),
+        _ => false,
+    }
+}
+
+// Fila desde la que cuenta el nivel de `node`. Un `block` de Python empieza en su primera
+// sentencia, así que cuenta la cabecera que lo abre (`def`, `if`, `else`...)
+fn opening_row(language: &str, node: &Node) -> usize {
+    match node.parent() {
+        Some(header) if language == "python" && node.kind() == "block" => header.start_position().row,
+        _ => node.start_position().row,
+    }
+}
+
+fn is_closing(node: &Node) -> bool {
+    !node.is_named() && matches!(node.kind(), "}" | ")" | "]")
+}
+
+/// Reindentación guiada por el árbol: cada línea lleva un nivel por cada nodo de bloque
+/// abierto en una línea anterior, salvo la que empieza con su corchete de cierre. Las líneas
+/// que continúan un token multilínea (comentarios, raw strings) se dejan como están, y los
+/// saltos CRLF se conservan. En Python la indentación es sintaxis: los niveles salen de los
+/// bloques que ya ve el parser, y con errores en el árbol o en líneas de comentario sólo se
+/// limpian los espacios finales.
+pub fn reindent(language: &str, text: &str, tree: &Tree, options: &FormatOptions) -> String {
+    let unit = if options.use_tabs { "\t".to_string() } else { " ".repeat(options.indent_width) };
+    let python = language == "python";
+    let reindents = ["compound_statement", "statement_block", "block"].iter().any(|kind| indents(language, kind))
+        && !(python && tree.root_node().has_error());
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let mut result = String::with_capacity(text.len());
+    let mut line_start = 0;
+    for (row, raw) in text.split('\n').enumerate() {
+        if row > 0 {
+            result.push('\n');
+        }
+        let (line, carriage_return) = match raw.strip_suffix('\r') {
+            Some(line) => (line, "\r"),
+            None => (raw, ""),
+        };
+        let content = line.trim();
+        let first = line_start + (line.len() - line.trim_start().len());
+        let leaf = tree.root_node().descendant_for_byte_range(first, first + 1);
+        line_start += raw.len() + 1;
+
+        match leaf {
+            _ if content.is_empty() => {}
+            Some(leaf) if leaf.start_position().row < row => result.push_str(line.trim_end()),
+            Some(leaf) if python && leaf.kind() == "comment" => result.push_str(line.trim_end()),
+            Some(leaf) if reindents => {
+                let mut depth = 0;
+                let mut node = leaf;
+                while let Some(parent) = node.parent() {
+                    let closes_parent = node.id() == leaf.id() && is_closing(&leaf);
+                    if indents(language, parent.kind()) && opening_row(language, &parent) < row && !closes_parent {
+                        depth += 1;
+                    }
+                    node = parent;
+                }
+                result.push_str(&unit.repeat(depth));
+                result.push_str(content);
+            }
+            _ => result.push_str(line.trim_end()),
+        }
+        result.push_str(carriage_return);
+    }
+
+    if options.insert_final_newline {
+        let trimmed = result.trim_end_matches(['\r', '\n']).len();
+        result.truncate(trimmed);
+        if !result.is_empty() {
+            result.push_str(newline);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+    use crate::types::apply_edits;
+
+    #[test]
+    fn reindents_cpp_with_minimal_edits() {
+        let code = "int main() {\nint x = 1;   \n  if (x) {\n        f(x,\n1);\n    }\n/* a\n     b */\n}\n\n\n";
+        let document = Document::for_test("cpp", code, "");
+        let options = FormatOptions { indent_width: 2, external: false, ..FormatOptions::default() };
+
+        let result = format_document("cpp", code, &document.tree, &options);
+        assert_eq!(result.formatter, "tree-sitter");
+        assert_eq!(apply_edits(code, &result.edits), "int main() {\n  int x = 1;\n  if (x) {\n    f(x,\n      1);\n  }\n  /* a\n     b */\n}\n");
+        // Sólo se tocan los espacios: el primer edit inserta la indentación de `int x`
+        assert_eq!(result.edits[0], TextEdit::new(13, 13, "  "));
+    }
+
+    #[test]
+    fn reindents_python_blocks_and_keeps_crlf() {
+        let code = "def f(a,\r\n        b):\r\n  if a:\r\n      return [\r\n   b]\r\n  # done\r\n  else:\r\n   return a  \r\n\r\n";
+        let document = Document::for_test("python", code, "");
+        let options = FormatOptions { external: false, ..FormatOptions::default() };
+
+        let formatted = reindent("python", code, &document.tree, &options);
+        assert_eq!(formatted, "def f(a,\r\n    b):\r\n    if a:\r\n        return [\r\n            b]\r\n  # done\r\n    else:\r\n        return a\r\n");
+
+        let broken = "def f(:\n  x\n";
+        let document = Document::for_test("python", broken, "");
+        assert_eq!(reindent("python", broken, &document.tree, &options), broken);
+    }
+}
@@ -1,85 +1,182 @@
+use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
-use anyhow::Error;
-use jni::{objects::{GlobalRef, JClass, JString, JValue}, sys::jobject, JNIEnv, JavaVM};
+use jni::{objects::{GlobalRef, JClass, JObject, JString, JValue}, sys::jobject, JNIEnv, JavaVM};
 use once_cell::sync::OnceCell;
 use tokio::runtime::{Runtime, Builder};
-use crate::code_exec::execute_code;
+use tokio::sync::oneshot;
+use crate::code_exec::{execute_code, execute_code_detailed};
+use crate::error::{jni_guard, panic_message, throw_error, RunixError, RunixResult};
+use crate::jni_bridge::{get_rust_string, to_json};
 // Globally store the JavaVM and Tokio runtime
 static JVM: OnceCell<Arc<JavaVM>> = OnceCell::new();
 static TOKIO_RT: OnceCell<Runtime> = OnceCell::new();
+// Clases de excepción resueltas en `JNI_OnLoad`: en un hilo adjuntado desde Rust, `FindClass`
+// usa el class loader del sistema y no encuentra las clases de la app
+static EXCEPTION_CLASSES: OnceCell<HashMap<&'static str, GlobalRef>> = OnceCell::new();
+const FALLBACK_EXCEPTION: &str = "java/lang/RuntimeException";
 
 #[unsafe(no_mangle)]
 pub extern "system" fn JNI_OnLoad(
     vm: JavaVM,
     _reserved: *mut std::ffi::c_void,
 ) -> jni::sys::jint {
-    JVM.set(Arc::new(vm)).expect("Failed to set JavaVM");
-    //TOKIO_RT.get_or_init(|| Runtime::new().expect("Failed to create Tokio runtime"));
+    std::panic::catch_unwind(move || {
+        match vm.get_env() {
+            Ok(mut env) => {
+                let _ = EXCEPTION_CLASSES.set(resolve_exception_classes(&mut env));
+            }
+            Err(e) => log::error!("Failed to get JNIEnv in JNI_OnLoad: {:?}", e),
+        }
+        if JVM.set(Arc::new(vm)).is_err() {
+            log::warn!("JavaVM was already initialized");
+        }
+        runtime();
+        jni::sys::JNI_VERSION_1_6
+    }).unwrap_or(jni::sys::JNI_ERR)
+}
+
+// Referencias globales a las clases de excepción; las que falten se omiten y se usará
+// `FALLBACK_EXCEPTION` al completar el future
+fn resolve_exception_classes(env: &mut JNIEnv) -> HashMap<&'static str, GlobalRef> {
+    let mut classes = HashMap::new();
+    for name in RunixError::JAVA_CLASSES.into_iter().chain([FALLBACK_EXCEPTION]) {
+        match env.find_class(name).and_then(|class| env.new_global_ref(class)) {
+            Ok(class) => {
+                classes.insert(name, class);
+            }
+            Err(e) => {
+                let _ = env.exception_clear();
+                log::warn!("Exception class {} not found: {:?}", name, e);
+            }
+        }
+    }
+    classes
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn JNI_OnUnload(_vm: *mut jni::sys::JavaVM, _reserved: *mut std::ffi::c_void) {
+    log::debug!("JNI_OnUnload called. Cleaning service")
+}
+
+/// Runtime compartido por todas las llamadas asíncronas. Se crea en `JNI_OnLoad`,
+/// pero se inicializa bajo demanda si se usa antes (por ejemplo, en tests).
+pub fn runtime() -> &'static Runtime {
     TOKIO_RT.get_or_init(|| Builder::new_multi_thread()
         .enable_all()
         .worker_threads(1)
         .build()
         .expect("Error building runtime")
-    );
-    jni::sys::JNI_VERSION_1_6
+    )
 }
 
-#[unsafe(no_mangle)]
-pub extern "system" fn JNI_OnUnload(_vm: *mut jni::sys::JavaVM, _reserved: *mut std::ffi::c_void) {
-    log::debug!("JNI_OnUnload called. Cleaning service")
+/// Lanza `task` en el runtime compartido y bloquea el hilo actual hasta que termine.
+/// No debe llamarse desde un hilo del propio runtime.
+pub fn block_on_shared<T, F>(task: F) -> RunixResult<T>
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    runtime().spawn(async move {
+        let _ = tx.send(join_task(task).await);
+    });
+    rx.blocking_recv()
+        .map_err(|_| RunixError::Execution("execution task was cancelled".to_string()))?
+}
+
+// Ejecuta `task` como tarea propia para que un pánico dentro de ella se reporte como error
+async fn join_task<T, F>(task: F) -> RunixResult<T>
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    match tokio::spawn(task).await {
+        Ok(value) => Ok(value),
+        Err(e) if e.is_panic() => {
+            Err(RunixError::Execution(format!("task panicked: {}", panic_message(&*e.into_panic()))))
+        }
+        Err(_) => Err(RunixError::Execution("execution task was cancelled".to_string())),
+    }
 }
 
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_ktFuture(
+    env: JNIEnv,
+    class: JClass,
+    code: JString,
+    language_name: JString,
+    input: JString,
+) -> jobject {
+    Java_com_example_lancelot_rust_RustBridge_executeCodeAsync(env, class, code, language_name, input)
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_executeCodeAsync(
     mut env: JNIEnv,
     _class: JClass,
     code: JString,
     language_name: JString,
     input: JString,
 ) -> jobject {
-    // 1. Extraer cadenas en el hilo JNI
-    let code_str: Result<String, Error> = match env.get_string(&code) {
-        Ok(java_str) => match java_str.to_str() {
-            Ok(cs) => Ok(cs.to_string()),
-            Err(e) => {
-                let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Invalid code string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        },
-        Err(e) => {
-            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Failed to get code string: {:?}", e));
+    jni_guard(&mut env, |env| {
+        let Some((code, lang, input)) = get_execution_args(env, &code, &language_name, &input) else {
             return std::ptr::null_mut();
-        }
-    };
+        };
 
-    let lang_str: Result<String, Error> = match env.get_string(&language_name) {
-        Ok(java_str) => match java_str.to_str() {
-            Ok(cs) => Ok(cs.to_string()),
-            Err(e) => {
-                let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Invalid code string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        },
-        Err(e) => {
-            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Failed to get code string: {:?}", e));
+        spawn_completable(env, async move {
+            execute_code(&code, &lang, &input).await
+        })
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_executeCodeDetailedAsync(
+    mut env: JNIEnv,
+    _class: JClass,
+    code: JString,
+    language_name: JString,
+    input: JString,
+) -> jobject {
+    jni_guard(&mut env, |env| {
+        let Some((code, lang, input)) = get_execution_args(env, &code, &language_name, &input) else {
             return std::ptr::null_mut();
-        }
-    };
-    let input_str: Result<String, Error> = match env.get_string(&input) {
-        Ok(java_str) => match java_str.to_str() {
-            Ok(cs) => Ok(cs.to_string()),
-            Err(e) => {
-                let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Invalid code string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        },
+        };
+
+        spawn_completable(env, async move {
+            execute_code_detailed(&code, &lang, &input).await
+                .and_then(|res| to_json(&res, "execution result"))
+        })
+    })
+}
+
+// Extrae los argumentos de ejecución en el hilo JNI; lanza la excepción Java si falla
+fn get_execution_args(
+    env: &mut JNIEnv,
+    code: &JString,
+    language_name: &JString,
+    input: &JString,
+) -> Option<(String, String, String)> {
+    let args = (|| Ok((
+        get_rust_string(env, code, "code")?,
+        get_rust_string(env, language_name, "language_name")?,
+        get_rust_string(env, input, "input")?,
+    )))();
+
+    match args {
+        Ok(args) => Some(args),
         Err(e) => {
-            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Failed to get code string: {:?}", e));
-            return std::ptr::null_mut();
+            throw_error(env, &e);
+            None
         }
-    };
+    }
+}
 
-    // 2. Crear y registrar un CompletableFuture
+// Crea un CompletableFuture, lanza `task` en el runtime global y lo completa al terminar
+fn spawn_completable<F>(env: &mut JNIEnv, task: F) -> jobject
+where
+    F: Future<Output = RunixResult<String>> + Send + 'static,
+{
     let future_obj = match env.new_object(
         "java/util/concurrent/CompletableFuture",
         "()V",
@@ -87,29 +184,27 @@ pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_ktFuture(
     ) {
         Ok(o) => o,
         Err(e) => {
-            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Failed to create CompletableFuture: {:?}", e));
+            throw_error(env, &RunixError::from(e));
             return std::ptr::null_mut();
         }
     };
-    let future_ref = match env.new_global_ref(future_obj) {
-        Ok(r) => Arc::new(r),
+    let future_ref = match env.new_global_ref(&future_obj) {
+        Ok(r) => r,
         Err(e) => {
-            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Failed to create global ref: {:?}", e));
+            throw_error(env, &RunixError::from(e));
+            return std::ptr::null_mut();
+        }
+    };
+    let jvm = match JVM.get() {
+        Some(vm) => Arc::clone(vm),
+        None => {
+            throw_error(env, &RunixError::Jni("JavaVM not initialized".to_string()));
             return std::ptr::null_mut();
         }
     };
-    let future_ref_clone = future_ref.clone();
-
-    // 3. Capturar JavaVM y Runtime para el hilo de fondo
-    let jvm = JVM.get().expect("JavaVM not initialized").clone();
-    let code_owned = code_str.unwrap();
-    let lang_owned = lang_str.unwrap();
-    let input_owned = input_str.unwrap();
 
-    // 4. Lanzar tarea en el runtime global
-    TOKIO_RT.get().unwrap().spawn(async move {
-        // Ejecutar lógica asíncrona
-        let res: Result<String, String> = execute_code(&code_owned, &lang_owned, &input_owned).await;
+    runtime().spawn(async move {
+        let res = join_task(task).await.and_then(|res| res);
 
         // Adjuntar el hilo a JVM y completar el future
         let env = match jvm.attach_current_thread_permanently() {
@@ -119,20 +214,20 @@ pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_ktFuture(
                 return;
             }
         };
-        if let Err(e) = complete_future(env, &future_ref_clone, res) {
+        if let Err(e) = complete_future(env, &future_ref, res) {
             log::error!("Error completing future: {:?}", e);
         }
     });
 
-    // 5. Devolver el CompletableFuture al código Java (retorna jobject)
-    future_ref.as_obj().as_raw()
+    // Devolvemos la referencia local; la global se libera al completar el future
+    future_obj.into_raw()
 }
 
-// Helper para completar el future con resultado o excepción
+// Helper para completar el future con resultado o con la excepción tipada del error
 fn complete_future(
     mut env: JNIEnv,
     future: &GlobalRef,
-    result: Result<String, String>,
+    result: RunixResult<String>,
 ) -> Result<(), jni::errors::Error> {
     match result {
         Ok(output) => {
@@ -144,13 +239,15 @@ fn complete_future(
                 &[JValue::Object(&jstr)],
             )?;
         }
-        Err(err_msg) => {
-            let jstr = env.new_string(err_msg)?;
-            let exception = env.new_object(
-                "java/util/concurrent/CompletionException",
-                "(Ljava/lang/String;)V",
-                &[JValue::Object(&jstr)],
-            )?;
+        Err(err) => {
+            let exception = match new_exception(&mut env, err.java_class(), &err) {
+                Ok(exception) => exception,
+                Err(e) => {
+                    log::warn!("Failed to build {}: {:?}", err.java_class(), e);
+                    let _ = env.exception_clear();
+                    new_exception(&mut env, FALLBACK_EXCEPTION, &err)?
+                }
+            };
             env.call_method(
                 future.as_obj(),
                 "completeExceptionally",
@@ -161,3 +258,17 @@ fn complete_future(
     }
     Ok(())
 }
+
+// Instancia la excepción `class_name` con el mensaje de `err`, usando la clase cacheada
+fn new_exception<'local>(
+    env: &mut JNIEnv<'local>,
+    class_name: &str,
+    err: &RunixError,
+) -> Result<JObject<'local>, jni::errors::Error> {
+    let message = env.new_string(err.to_string())?;
+    let args = [JValue::Object(&message)];
+    match EXCEPTION_CLASSES.get().and_then(|classes| classes.get(class_name)) {
+        Some(class) => env.new_object(<&JClass>::from(class.as_obj()), "(Ljava/lang/String;)V", &args),
+        None => env.new_object(class_name, "(Ljava/lang/String;)V", &args),
+    }
+}
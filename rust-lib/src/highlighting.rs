@@ -1,22 +1,148 @@
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::time::Instant;
-use tree_sitter::{Tree, Language};
-use tree_sitter_highlight::HighlightEvent;
+use std::ops::Range;
+use tree_sitter::{Language, Node, Parser, Query, QueryCursor, StreamingIterator, Tree};
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
 use log::debug;
-use anyhow::Result;
 
+use crate::buffer::TextBuffer;
 use crate::cache::*;
+use crate::error::{RunixError, RunixResult};
+use crate::offsets::OffsetEncoding;
+use crate::theme::Theme;
 use crate::types::*;
 use crate::utils::*;
 
+// Nombres por defecto cuando Kotlin no envía una lista válida
+pub const DEFAULT_HIGHLIGHT_NAMES: [&str; 8] = [
+    "keyword", "function", "type", "string", "number", "comment", "constant", "variable",
+];
+
 // Core highlighting functions
 
-pub fn load_language(language_name: &str) -> Result<Language> {
+pub fn load_language(language_name: &str) -> RunixResult<Language> {
     match language_name {
         "cpp" => Ok(tree_sitter_cpp::LANGUAGE.into()),
         "javascript" => Ok(tree_sitter_javascript::LANGUAGE.into()),
         "python" => Ok(tree_sitter_python::LANGUAGE.into()),
-        _ => Err(anyhow::anyhow!("Unsupported language: {}", language_name))
+        _ => Err(RunixError::UnsupportedLanguage(language_name.to_string()))
+    }
+}
+
+/// Resalta `input` completo, reutilizando el cache incremental cuando es posible.
+pub fn highlight_code(
+    input: &str,
+    language_name: &str,
+    queries: &HighlightQueries,
+    highlight_names: &[String],
+) -> RunixResult<HighlightDelta> {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    language_name.hash(&mut hasher);
+    let input_hash = hasher.finish();
+
+    if let Some(cache) = get_highlight_cache(language_name) {
+        if cache.matches_input(input) {
+            if let Some(delta) = try_incremental_highlight(&cache, input, highlight_names) {
+                return Ok(delta);
+            }
+        }
+    }
+
+    let language = load_language(language_name)?;
+
+    let mut highlighter = Highlighter::new();
+    let mut config = HighlightConfiguration::new(
+        language.clone(),
+        "highlighter", // Name for the configuration, can be arbitrary
+        queries.highlights,
+        queries.injections,
+        queries.locals,
+    )?;
+
+    let highlight_name_refs: Vec<&str> = highlight_names.iter().map(|s| s.as_str()).collect();
+    config.configure(&highlight_name_refs);
+
+    let old_tree_arc_opt = PARSE_CACHE.lock_or_recover()
+        .get(&input_hash)
+        .filter(|(_, cached_input)| cached_input == input)
+        .map(|(tree, _)| Arc::clone(tree));
+
+    let new_tree = {
+        let mut parser_cache_guard = PARSER_CACHE.lock_or_recover();
+        let parser = match parser_cache_guard.entry(language_name.to_string()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                debug!("Creating new parser for {}", language_name);
+                let mut p = Parser::new();
+                p.set_language(&language)
+                    .map_err(|e| RunixError::UnsupportedLanguage(format!("{}: {}", language_name, e)))?;
+                entry.insert(p)
+            }
+        };
+
+        match parser.parse(input, old_tree_arc_opt.as_deref()) {
+            Some(tree) => Arc::new(tree),
+            None => return Err(RunixError::Parse(format!("parsing failed for language {}", language_name))),
+        }
+    };
+
+    let highlights_iter = highlighter.highlight(
+        &config,
+        input.as_bytes(),
+        None, // No injection callback needed for this basic setup
+        |_| None // No injection callback
+    )?;
+
+    let (highlight_delta, ranges) = process_highlights(highlights_iter, highlight_names.to_vec(), Arc::clone(&new_tree), input)?;
+
+    let start_time = Instant::now();
+    update_highlight_cache(language_name.to_string(), input_hash, Arc::clone(&new_tree), input.to_string(), ranges, highlight_delta.clone());
+    debug!("Cache update completed in {:?}", start_time.elapsed());
+
+    Ok(highlight_delta)
+}
+
+/// Traduce los offsets UTF-8 de `delta` a la codificación pedida, añade puntos fila/columna
+/// y los estilos resueltos con `theme`. El cache siempre guarda bytes sin estilos; esto se
+/// aplica sólo a la copia que sale hacia Kotlin.
+pub fn apply_highlight_options(delta: &mut HighlightDelta, text: &str, options: &HighlightOptions, theme: &Theme) {
+    if options.style_output != StyleOutput::None {
+        let table = theme.resolve_styles(&delta.highlight_names);
+        for range in delta.ranges.iter_mut() {
+            range.style = match options.style_output {
+                StyleOutput::Ids => table.highlight_styles.get(range.highlight_type).copied().flatten().map(|id| id as u32),
+                StyleOutput::Argb => table.style_for_highlight(range.highlight_type).and_then(|style| style.foreground),
+                StyleOutput::None => None,
+            };
+        }
+        if options.style_output == StyleOutput::Ids {
+            delta.styles = Some(table.styles);
+        }
+    }
+
+    if options.offset_encoding == OffsetEncoding::Utf8 && !options.include_points {
+        return;
+    }
+
+    let buffer = TextBuffer::new(text);
+    let encoding = options.offset_encoding;
+    for range in delta.ranges.iter_mut() {
+        if options.include_points {
+            range.start_point = Some(buffer.byte_to_point(range.start, encoding));
+            range.end_point = Some(buffer.byte_to_point(range.end, encoding));
+        }
+        range.start = buffer.convert(range.start, encoding);
+        range.end = buffer.convert(range.end, encoding);
+    }
+
+    let convert_pair = |&(start, end): &(usize, usize)| (buffer.convert(start, encoding), buffer.convert(end, encoding));
+    delta.changed_ranges = delta.changed_ranges.iter().map(convert_pair).collect();
+    if let Some(reused) = delta.reused_ranges.as_mut() {
+        *reused = reused.iter().map(convert_pair).collect();
     }
 }
 
@@ -25,7 +151,7 @@ pub fn process_highlights(
     highlight_names: Vec<String>, 
     tree: Arc<Tree>,
     text: &str
-) -> (HighlightDelta, Vec<HighlightRange>) {
+) -> RunixResult<(HighlightDelta, Vec<HighlightRange>)> {
     let _start_time = Instant::now(); 
     let estimated_capacity = text.len() / 20; 
     let mut ranges = Vec::with_capacity(estimated_capacity);
@@ -33,14 +159,10 @@ pub fn process_highlights(
     
     debug!("Starting highlight processing for {} bytes", text.len());
     for event_result in highlights {
-        match event_result.unwrap() {
+        match event_result? {
             HighlightEvent::Source { start: s, end } => {
                 if let Some(typ) = current_type {
-                    ranges.push(HighlightRange {
-                        start: s,
-                        end,
-                        highlight_type: typ
-                    });
+                    ranges.push(HighlightRange::new(s, end, typ));
                 }
             },
             HighlightEvent::HighlightStart(highlight_info) => {
@@ -57,13 +179,11 @@ pub fn process_highlights(
     let mut version = 0;
     let mut reused_ranges = None;
     
-    if let Ok(cache_guard) = HIGHLIGHT_CACHE.lock() {
-        if let Some(cache) = cache_guard.get(&cache_key) {
-            if text_difference_ratio(&cache.input, text) < 0.3 {
-                changed_ranges = get_text_changes(&cache.input, text);
-                reused_ranges = Some(get_reused_ranges(&changed_ranges, text.len()));
-                version = cache.version + 1;
-            }
+    if let Some(cache) = HIGHLIGHT_CACHE.lock_or_recover().get(&cache_key) {
+        if text_difference_ratio(&cache.input, text) < 0.3 {
+            changed_ranges = get_text_changes(&cache.input, text);
+            reused_ranges = Some(get_reused_ranges(&changed_ranges, text.len()));
+            version = cache.version + 1;
         }
     }
     
@@ -72,10 +192,259 @@ pub fn process_highlights(
         highlight_names,
         reused_ranges,
         version: version + 1,
-        changed_ranges
+        changed_ranges,
+        styles: None
     };
 
-    (delta, ranges)
+    Ok((delta, ranges))
+}
+
+/// Índice del nombre de resaltado que corresponde a una captura, con la misma regla que
+/// `HighlightConfiguration::configure`: gana el nombre con más partes contenidas en la captura.
+pub fn match_highlight_name(capture_name: &str, highlight_names: &[String]) -> Option<usize> {
+    let capture_parts: Vec<&str> = capture_name.split('.').collect();
+    let mut best_index = None;
+    let mut best_match_len = 0;
+    for (i, name) in highlight_names.iter().enumerate() {
+        let parts: Vec<&str> = name.split('.').collect();
+        if parts.iter().all(|part| capture_parts.contains(part)) && parts.len() > best_match_len {
+            best_index = Some(i);
+            best_match_len = parts.len();
+        }
+    }
+    best_index
+}
+
+/// Resaltador basado en `highlights.scm` y `locals.scm` que trabaja sobre un árbol ya
+/// parseado y puede limitarse a un rango de bytes (el viewport del editor).
+///
+/// Resuelve las capturas como `highlight_code` (tree-sitter-highlight 0.25): en un mismo
+/// nodo cada patrón posterior sustituye al anterior, y una referencia a una variable local
+/// toma el resaltado de su definición. No aplica inyecciones, que tampoco se usan allí.
+pub struct QueryHighlighter {
+    query: Query,
+    capture_highlights: Vec<Option<usize>>,
+    // Patrones con `(#is-not? local)`, que se ignoran en definiciones y referencias locales
+    non_local_patterns: Vec<bool>,
+    locals: Option<LocalsQuery>,
+}
+
+// Capturas de un nodo como (patrón, resaltado)
+type NodeCaptures = Vec<(usize, Option<usize>)>;
+
+struct LocalsQuery {
+    query: Query,
+    scope: Option<u32>,
+    definition: Option<u32>,
+    definition_value: Option<u32>,
+    reference: Option<u32>,
+}
+
+/// Variables locales de una versión del árbol (ids de nodo): las definiciones y, para cada
+/// referencia resuelta, el resaltado de su definición.
+#[derive(Debug, Default)]
+pub struct LocalBindings {
+    definitions: HashSet<usize>,
+    references: HashMap<usize, usize>,
+}
+
+// Ámbito abierto durante la resolución de locals; `definitions` guarda (nombre, fin del
+// valor, índice en la lista de definiciones)
+struct LocalScope {
+    end: usize,
+    inherits: bool,
+    definitions: Vec<(String, usize, usize)>,
+}
+
+impl QueryHighlighter {
+    pub fn new(language: &Language, highlights_scm: &str, locals_scm: &str, highlight_names: &[String]) -> RunixResult<Self> {
+        let query = Query::new(language, highlights_scm)?;
+        let capture_highlights = query.capture_names().iter()
+            .map(|name| match_highlight_name(name, highlight_names))
+            .collect();
+        let non_local_patterns = (0..query.pattern_count())
+            .map(|pattern| query.property_predicates(pattern).iter()
+                .any(|(property, positive)| !positive && &*property.key == "local"))
+            .collect();
+        let locals = if locals_scm.trim().is_empty() {
+            None
+        } else {
+            let query = Query::new(language, locals_scm)?;
+            Some(LocalsQuery {
+                scope: query.capture_index_for_name("local.scope"),
+                definition: query.capture_index_for_name("local.definition"),
+                definition_value: query.capture_index_for_name("local.definition-value"),
+                reference: query.capture_index_for_name("local.reference"),
+                query,
+            })
+        };
+        Ok(Self { query, capture_highlights, non_local_patterns, locals })
+    }
+
+    /// Resuelve las variables locales de todo el árbol. Depende sólo de la versión del
+    /// documento, así que `Document` la calcula una vez por edición.
+    pub fn resolve_locals(&self, tree: &Tree, buffer: &TextBuffer) -> LocalBindings {
+        let mut bindings = LocalBindings::default();
+        let Some(locals) = self.locals.as_ref().filter(|locals| locals.definition.is_some()) else {
+            return bindings;
+        };
+
+        // Definiciones (nodo) y referencias (nodo, índice de la definición) en orden de documento
+        let mut definitions: Vec<usize> = Vec::new();
+        let mut references: Vec<(usize, usize)> = Vec::new();
+        let mut scopes = vec![LocalScope { end: usize::MAX, inherits: false, definitions: Vec::new() }];
+        let mut cursor = QueryCursor::new();
+        let mut iter = cursor.captures(&locals.query, tree.root_node(), |node: Node| buffer.chunks(node.byte_range()));
+        while let Some((query_match, capture_index)) = iter.next() {
+            let capture = query_match.captures[*capture_index];
+            let node = capture.node;
+            while scopes.len() > 1 && node.start_byte() > scopes[scopes.len() - 1].end {
+                scopes.pop();
+            }
+            if Some(capture.index) == locals.scope {
+                let inherits = locals.query.property_settings(query_match.pattern_index).iter()
+                    .find(|property| &*property.key == "local.scope-inherits")
+                    .is_none_or(|property| property.value.as_deref().is_none_or(|value| value == "true"));
+                scopes.push(LocalScope { end: node.end_byte(), inherits, definitions: Vec::new() });
+            } else if Some(capture.index) == locals.definition {
+                let value_end = query_match.captures.iter()
+                    .find(|c| Some(c.index) == locals.definition_value)
+                    .map_or(0, |c| c.node.end_byte());
+                let name = buffer.slice(node.byte_range());
+                if let Some(scope) = scopes.last_mut() {
+                    scope.definitions.push((name, value_end, definitions.len()));
+                }
+                definitions.push(node.id());
+                bindings.definitions.insert(node.id());
+            } else if Some(capture.index) == locals.reference && !bindings.definitions.contains(&node.id()) {
+                let name = buffer.slice(node.byte_range());
+                for scope in scopes.iter().rev() {
+                    let found = scope.definitions.iter().rev()
+                        .find(|(def_name, value_end, _)| *def_name == name && node.start_byte() >= *value_end);
+                    if let Some(&(_, _, index)) = found {
+                        references.push((node.id(), index));
+                        break;
+                    }
+                    if !scope.inherits {
+                        break;
+                    }
+                }
+            }
+        }
+        if references.is_empty() {
+            return bindings;
+        }
+
+        // Resaltado de cada definición según highlights.scm, tratándola como local
+        let mut definition_captures: HashMap<usize, NodeCaptures> = HashMap::new();
+        let mut cursor = QueryCursor::new();
+        let mut iter = cursor.captures(&self.query, tree.root_node(), |node: Node| buffer.chunks(node.byte_range()));
+        while let Some((query_match, capture_index)) = iter.next() {
+            let capture = query_match.captures[*capture_index];
+            if bindings.definitions.contains(&capture.node.id()) {
+                definition_captures.entry(capture.node.id()).or_default()
+                    .push((query_match.pattern_index, self.capture_highlights[capture.index as usize]));
+            }
+        }
+        for (reference, index) in references {
+            let highlight = definition_captures.get_mut(&definitions[index])
+                .and_then(|captures| self.resolve(captures, true));
+            if let Some(highlight) = highlight {
+                bindings.references.insert(reference, highlight);
+            }
+        }
+        bindings
+    }
+
+    /// Devuelve los rangos resaltados que intersecan `range`, recortados a él.
+    pub fn highlight_range(&self, tree: &Tree, buffer: &TextBuffer, range: Range<usize>, locals: &LocalBindings) -> Vec<HighlightRange> {
+        let start = range.start.min(buffer.len());
+        let end = range.end.min(buffer.len());
+        if start >= end {
+            return Vec::new();
+        }
+
+        // Capturas agrupadas por nodo, en el orden en que las entrega la query
+        let mut nodes: Vec<(Node, NodeCaptures)> = Vec::new();
+        let mut index_of: HashMap<usize, usize> = HashMap::new();
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(start..end);
+        let mut iter = cursor.captures(&self.query, tree.root_node(), |node: Node| buffer.chunks(node.byte_range()));
+        while let Some((query_match, capture_index)) = iter.next() {
+            let capture = query_match.captures[*capture_index];
+            let slot = *index_of.entry(capture.node.id()).or_insert_with(|| {
+                nodes.push((capture.node, Vec::new()));
+                nodes.len() - 1
+            });
+            nodes[slot].1.push((query_match.pattern_index, self.capture_highlights[capture.index as usize]));
+        }
+
+        let mut spans: Vec<(usize, usize, usize, usize)> = nodes.into_iter()
+            .filter_map(|(node, mut captures)| {
+                let highlight = if locals.definitions.contains(&node.id()) {
+                    self.resolve(&mut captures, true)
+                } else if let Some(&highlight) = locals.references.get(&node.id()) {
+                    Some(highlight)
+                } else {
+                    self.resolve(&mut captures, false)
+                }?;
+                Some((node.start_byte().max(start), node.end_byte().min(end), node.descendant_count(), highlight))
+            })
+            .collect();
+        sweep_spans(&mut spans)
+    }
+
+    // Resaltado final de un nodo a partir de sus capturas (patrón, resaltado): cada patrón
+    // posterior sustituye al anterior, también si su captura no tiene nombre configurado
+    // (el nodo queda entonces sin resaltar). En nodos locales se saltan los patrones
+    // marcados con `(#is-not? local)`.
+    fn resolve(&self, captures: &mut [(usize, Option<usize>)], local: bool) -> Option<usize> {
+        captures.sort_by_key(|&(pattern, _)| pattern);
+        captures.iter()
+            .rfind(|&&(pattern, _)| !(local && self.non_local_patterns[pattern]))
+            .and_then(|&(_, highlight)| highlight)
+    }
+}
+
+// Convierte spans anidados (inicio, fin, descendientes, resaltado) en rangos planos donde
+// manda el span más interno. Ordenados por inicio y, a igual inicio, del más externo al más
+// interno (con rangos idénticos, el ancestro tiene más descendientes).
+fn sweep_spans(spans: &mut [(usize, usize, usize, usize)]) -> Vec<HighlightRange> {
+    spans.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(b.2.cmp(&a.2)));
+
+    let mut ranges: Vec<HighlightRange> = Vec::new();
+    let mut emit = |from: usize, to: usize, highlight: usize| {
+        if from >= to {
+            return;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.end == from && last.highlight_type == highlight => last.end = to,
+            _ => ranges.push(HighlightRange::new(from, to, highlight)),
+        }
+    };
+    // Pila de (fin, resaltado) de los spans abiertos; `position` es hasta dónde se ha emitido
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut position = 0;
+    for &(span_start, span_end, _, highlight) in spans.iter() {
+        while let Some(&(open_end, open_highlight)) = open.last() {
+            if open_end > span_start {
+                break;
+            }
+            emit(position, open_end, open_highlight);
+            position = position.max(open_end);
+            open.pop();
+        }
+        if let Some(&(_, open_highlight)) = open.last() {
+            emit(position, span_start, open_highlight);
+        }
+        position = position.max(span_start);
+        open.push((span_end, highlight));
+    }
+    while let Some((open_end, open_highlight)) = open.pop() {
+        emit(position, open_end, open_highlight);
+        position = position.max(open_end);
+    }
+    ranges
 }
 
 pub fn get_language_from_tree(tree: &Tree) -> String {
@@ -99,7 +468,8 @@ pub fn try_incremental_highlight(
             highlight_names: highlight_names.to_vec(),
             reused_ranges: Some(vec![(0, new_text.len())]),
             version: cache.version,
-            changed_ranges: vec![]
+            changed_ranges: vec![],
+            styles: None
         });
     }
     
@@ -118,7 +488,8 @@ pub fn try_incremental_highlight(
             highlight_names: highlight_names.to_vec(),
             reused_ranges: Some(vec![(0, new_text.len())]),
             version: cache.version,
-            changed_ranges: vec![]
+            changed_ranges: vec![],
+            styles: None
         });
     }
     
@@ -165,7 +536,8 @@ pub fn try_incremental_highlight(
         highlight_names: highlight_names.to_vec(),
         reused_ranges: Some(reused_ranges),
         version: cache.version + 1,
-        changed_ranges
+        changed_ranges,
+        styles: None
     };
 
     debug!("Incremental highlight completed in {:?}", start_time.elapsed());
@@ -185,4 +557,75 @@ mod tests {
     fn load_language_unknown() {
         assert!(load_language("unknown").is_err());
     }
+
+    #[test]
+    fn highlight_range_is_limited_to_viewport() {
+        let language = load_language("cpp").unwrap();
+        let names = vec!["keyword".to_string(), "number".to_string()];
+        let highlighter = QueryHighlighter::new(&language, "\"return\" @keyword (number_literal) @number", "", &names).unwrap();
+        let text = "int f() { return 1; }\nint g() { return 2; }";
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(text, None).unwrap();
+
+        let second_line = text.find('\n').unwrap() + 1;
+        let ranges = highlighter.highlight_range(&tree, &TextBuffer::new(text), second_line..text.len(), &LocalBindings::default());
+        let spans: Vec<&str> = ranges.iter().map(|r| &text[r.start..r.end]).collect();
+        assert_eq!(spans, vec!["return", "2"]);
+        assert_eq!(ranges[0].highlight_type, 0);
+    }
+
+    #[test]
+    fn highlight_range_splits_nested_captures() {
+        let language = load_language("cpp").unwrap();
+        let names = vec!["string".to_string(), "escape".to_string()];
+        let highlighter = QueryHighlighter::new(&language, "(string_literal) @string (escape_sequence) @escape", "", &names).unwrap();
+        let text = "auto s = \"a\\nb\";";
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(text, None).unwrap();
+
+        let ranges = highlighter.highlight_range(&tree, &TextBuffer::new(text), 0..text.len(), &LocalBindings::default());
+        let runs: Vec<(&str, usize)> = ranges.iter().map(|r| (&text[r.start..r.end], r.highlight_type)).collect();
+        assert_eq!(runs, vec![("\"a", 0), ("\\n", 1), ("b\"", 0)]);
+    }
+
+    #[test]
+    fn highlight_range_applies_locals_like_highlight_code() {
+        let language = load_language("cpp").unwrap();
+        let names = vec!["variable".to_string(), "variable.parameter".to_string()];
+        let highlights = "(identifier) @variable\n(parameter_declaration declarator: (identifier) @variable.parameter)";
+        let locals = "(function_definition) @local.scope\n(parameter_declaration declarator: (identifier) @local.definition)\n(identifier) @local.reference";
+        let text = "int f(int x) { return x; }";
+        let (definition, reference) = (text.find('x').unwrap(), text.rfind('x').unwrap());
+
+        // Con tree-sitter-highlight el parámetro, capturado por los dos patrones, toma el
+        // último, y la referencia hereda el resaltado de la definición
+        let mut config = HighlightConfiguration::new(language.clone(), "cpp", highlights, "", locals).unwrap();
+        config.configure(&names);
+        let mut highlighter = Highlighter::new();
+        let mut stack = Vec::new();
+        let mut full = Vec::new();
+        for event in highlighter.highlight(&config, text.as_bytes(), None, |_| None).unwrap() {
+            match event.unwrap() {
+                HighlightEvent::HighlightStart(h) => stack.push(h.0),
+                HighlightEvent::HighlightEnd => { stack.pop(); }
+                HighlightEvent::Source { start, .. } if start == definition || start == reference => full.push(stack.last().copied()),
+                HighlightEvent::Source { .. } => {}
+            }
+        }
+        assert_eq!(full, vec![Some(1), Some(1)]);
+
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(text, None).unwrap();
+        let buffer = TextBuffer::new(text);
+        let highlighter = QueryHighlighter::new(&language, highlights, locals, &names).unwrap();
+        let bindings = highlighter.resolve_locals(&tree, &buffer);
+        let ranges = highlighter.highlight_range(&tree, &buffer, reference..text.len(), &bindings);
+        assert_eq!(ranges.first().map(|r| (r.start, r.highlight_type)), Some((reference, 1)));
+        let ranges = highlighter.highlight_range(&tree, &buffer, 0..text.len(), &bindings);
+        let at = |offset: usize| ranges.iter().find(|r| r.start == offset).map(|r| r.highlight_type);
+        assert_eq!(vec![at(definition), at(reference)], full);
+    }
 }
@@ -0,0 +1,368 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{RunixError, RunixResult};
+use crate::types::TextEdit;
+
+// Pulsaciones separadas por menos de esto se deshacen juntas
+const GROUP_TIMEOUT_MS: u64 = 1000;
+const DEFAULT_LIMIT: usize = 500;
+
+/// Origen de una edición; decide qué transacciones se agrupan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditKind {
+    Typing,
+    Delete,
+    Paste,
+    Snippet,
+    Format,
+    Other,
+}
+
+impl EditKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "typing" => Some(EditKind::Typing),
+            "delete" => Some(EditKind::Delete),
+            "paste" => Some(EditKind::Paste),
+            "snippet" => Some(EditKind::Snippet),
+            "format" => Some(EditKind::Format),
+            "other" => Some(EditKind::Other),
+            _ => None,
+        }
+    }
+
+    /// Clasifica un cambio sin origen explícito: borrar es `Delete`, escribir un carácter
+    /// (o un salto de línea con su indentación) es `Typing` y el resto, `Paste`.
+    pub fn classify(changes: &[Change]) -> Self {
+        match changes {
+            [change] if change.inserted.is_empty() => EditKind::Delete,
+            [change] if change.removed.is_empty() => {
+                let typed = change.inserted.trim_start_matches(['\r', '\n']);
+                if change.inserted.chars().count() == 1 || (typed.len() < change.inserted.len() && typed.trim().is_empty()) {
+                    EditKind::Typing
+                } else {
+                    EditKind::Paste
+                }
+            }
+            _ => EditKind::Other,
+        }
+    }
+}
+
+/// Reemplazo registrado: en `start` (bytes del texto previo) había `removed` y queda `inserted`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    pub start: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+/// Unidad de deshacer. Cada paso es una lista de cambios ordenados y sin solapes, con
+/// offsets del texto anterior a ese paso.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub kind: EditKind,
+    pub steps: Vec<Vec<Change>>,
+    /// Milisegundos desde epoch del último cambio agrupado
+    pub timestamp: u64,
+}
+
+impl Transaction {
+    // Edits que rehacen la transacción, en orden de aplicación
+    fn forward_edits(&self) -> Vec<TextEdit> {
+        self.steps.iter()
+            .flat_map(|step| step.iter().rev())
+            .map(|c| TextEdit::new(c.start, c.start + c.removed.len(), c.inserted.clone()))
+            .collect()
+    }
+
+    // Edits que la deshacen: pasos al revés y, en cada uno, los cambios desde el último
+    fn inverse_edits(&self) -> Vec<TextEdit> {
+        let mut edits = Vec::new();
+        for step in self.steps.iter().rev() {
+            let mut shift = 0isize;
+            let mut inverse = Vec::with_capacity(step.len());
+            for change in step {
+                let start = change.start.saturating_add_signed(shift);
+                inverse.push(TextEdit::new(start, start + change.inserted.len(), change.removed.clone()));
+                shift += change.inserted.len() as isize - change.removed.len() as isize;
+            }
+            edits.extend(inverse.into_iter().rev());
+        }
+        edits
+    }
+}
+
+// Funde `next` (sobre el texto que dejó `previous`) en `previous` si tocan el mismo tramo
+fn merge_changes(previous: &Change, next: &Change) -> Option<Change> {
+    let (a, inserted_end) = (previous.start, previous.start + previous.inserted.len());
+    let (c, d) = (next.start, next.start + next.removed.len());
+    if c > inserted_end || d < a {
+        return None;
+    }
+    let mut inserted = String::new();
+    let mut removed = String::new();
+    if c < a {
+        removed.push_str(next.removed.get(..a - c)?);
+    } else {
+        inserted.push_str(previous.inserted.get(..c - a)?);
+    }
+    removed.push_str(&previous.removed);
+    inserted.push_str(&next.inserted);
+    if d > inserted_end {
+        removed.push_str(next.removed.get(next.removed.len() - (d - inserted_end)..)?);
+    } else {
+        inserted.push_str(previous.inserted.get(d - a..)?);
+    }
+    Some(Change { start: a.min(c), removed, inserted })
+}
+
+/// Historial de deshacer/rehacer de un documento.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+    #[serde(skip)]
+    group: Option<Transaction>,
+    // Tras `seal` el siguiente cambio abre transacción aunque pudiera agruparse
+    #[serde(skip)]
+    sealed: bool,
+    #[serde(skip, default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    DEFAULT_LIMIT
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self { undo: Vec::new(), redo: Vec::new(), group: None, sealed: false, limit: DEFAULT_LIMIT }
+    }
+}
+
+/// Historial guardado junto con la huella del texto al que corresponde.
+#[derive(Serialize, Deserialize)]
+struct SavedHistory {
+    fingerprint: u64,
+    history: History,
+}
+
+// FNV-1a: estable entre versiones, a diferencia de `DefaultHasher`
+fn fingerprint(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+impl History {
+    pub fn undo_len(&self) -> usize {
+        self.undo.len() + usize::from(self.group.as_ref().is_some_and(|g| !g.steps.is_empty()))
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    pub fn record(&mut self, kind: EditKind, changes: Vec<Change>, now: u64) {
+        if changes.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        if let Some(group) = self.group.as_mut() {
+            Self::push_step(group, changes);
+            group.timestamp = now;
+            return;
+        }
+
+        let sealed = std::mem::take(&mut self.sealed);
+        if let Some(last) = self.undo.last_mut() {
+            let groupable = matches!(kind, EditKind::Typing | EditKind::Delete)
+                && last.kind == kind
+                && !sealed
+                && now.saturating_sub(last.timestamp) <= GROUP_TIMEOUT_MS;
+            if groupable && Self::merge_into_last_step(last, &changes) {
+                last.timestamp = now;
+                return;
+            }
+        }
+        self.undo.push(Transaction { kind, steps: vec![changes], timestamp: now });
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+    }
+
+    // Funde cambio a cambio si `changes` toca los mismos tramos que el último paso: la misma
+    // ráfaga con uno o varios cursores. Los offsets de `changes` ya incluyen lo que movieron
+    // los cambios anteriores del paso, así que se descuenta antes de fundir
+    fn merge_into_last_step(transaction: &mut Transaction, changes: &[Change]) -> bool {
+        let Some(previous) = transaction.steps.last_mut() else {
+            return false;
+        };
+        if previous.len() != changes.len() {
+            return false;
+        }
+        let mut shift = 0isize;
+        let mut merged: Vec<Change> = Vec::with_capacity(changes.len());
+        for (prev, next) in previous.iter().zip(changes) {
+            let Some(start) = next.start.checked_add_signed(-shift) else {
+                return false;
+            };
+            let Some(change) = merge_changes(prev, &Change { start, ..next.clone() }) else {
+                return false;
+            };
+            if merged.last().is_some_and(|last| last.start + last.removed.len() > change.start) {
+                return false;
+            }
+            shift += prev.inserted.len() as isize - prev.removed.len() as isize;
+            merged.push(change);
+        }
+        *previous = merged;
+        true
+    }
+
+    fn push_step(transaction: &mut Transaction, changes: Vec<Change>) {
+        if !Self::merge_into_last_step(transaction, &changes) {
+            transaction.steps.push(changes);
+        }
+    }
+
+    /// Agrupa en una sola transacción todo lo que se registre hasta `end_group`.
+    pub fn begin_group(&mut self, kind: EditKind, now: u64) {
+        self.end_group();
+        self.group = Some(Transaction { kind, steps: Vec::new(), timestamp: now });
+    }
+
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.group.take().filter(|g| !g.steps.is_empty()) {
+            self.undo.push(group);
+            self.sealed = true;
+        }
+    }
+
+    /// Corta la agrupación por tiempo (p. ej. al mover el cursor).
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Edits que deshacen la última transacción, en orden de aplicación: cada uno sobre el
+    /// texto que deja el anterior.
+    pub fn undo(&mut self) -> Option<Vec<TextEdit>> {
+        self.end_group();
+        let transaction = self.undo.pop()?;
+        let edits = transaction.inverse_edits();
+        self.redo.push(transaction);
+        self.sealed = true;
+        Some(edits)
+    }
+
+    pub fn redo(&mut self) -> Option<Vec<TextEdit>> {
+        self.end_group();
+        let transaction = self.redo.pop()?;
+        let edits = transaction.forward_edits();
+        self.undo.push(transaction);
+        self.sealed = true;
+        Some(edits)
+    }
+
+    /// JSON persistente; `text` es el contenido actual para validar la restauración.
+    pub fn save(&self, text: &str) -> RunixResult<String> {
+        let mut history = self.clone();
+        history.end_group();
+        serde_json::to_string(&SavedHistory { fingerprint: fingerprint(text), history })
+            .map_err(|e| RunixError::Jni(format!("failed to serialize history: {}", e)))
+    }
+
+    /// Restaura un historial guardado con `save`; falla si el texto ya no es el mismo.
+    pub fn restore(json: &str, text: &str) -> RunixResult<Self> {
+        let saved: SavedHistory = serde_json::from_str(json)
+            .map_err(|e| RunixError::InvalidArgument(format!("invalid history: {}", e)))?;
+        if saved.fingerprint != fingerprint(text) {
+            return Err(RunixError::InvalidArgument("history does not match the document text".into()));
+        }
+        Ok(Self { sealed: true, ..saved.history })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::apply_edits;
+
+    // Los edits de undo/redo son secuenciales: cada uno parte del texto que deja el anterior
+    fn apply(text: &mut String, edits: &[TextEdit]) {
+        for edit in edits {
+            *text = apply_edits(text, std::slice::from_ref(edit));
+        }
+    }
+
+    fn insert(start: usize, inserted: &str) -> Vec<Change> {
+        vec![Change { start, removed: String::new(), inserted: inserted.into() }]
+    }
+
+    #[test]
+    fn groups_typing_and_round_trips() {
+        let mut history = History::default();
+        let mut text = String::from("ab");
+        // "ab" -> "aXYb" tecleando, luego un backspace dentro de la ráfaga
+        history.record(EditKind::Typing, insert(1, "X"), 0);
+        history.record(EditKind::Typing, insert(2, "Y"), 200);
+        apply(&mut text, &[TextEdit::new(1, 1, "XY")]);
+        // Pasado el tiempo de agrupación, otra transacción
+        history.record(EditKind::Typing, insert(4, "!"), 5000);
+        text.push('!');
+        assert_eq!(history.undo_len(), 2);
+
+        history.begin_group(EditKind::Format, 6000);
+        history.record(EditKind::Other, vec![
+            Change { start: 0, removed: "a".into(), inserted: "A".into() },
+            Change { start: 3, removed: "b".into(), inserted: "BB".into() },
+        ], 6000);
+        apply(&mut text, &[TextEdit::new(3, 4, "BB"), TextEdit::new(0, 1, "A")]);
+        history.record(EditKind::Other, vec![Change { start: 5, removed: "!".into(), inserted: String::new() }], 6001);
+        text.truncate(5);
+        history.end_group();
+        assert_eq!(text, "AXYBB");
+
+        let saved = history.save(&text).unwrap();
+        let mut history = History::restore(&saved, &text).unwrap();
+        assert!(History::restore(&saved, "otro").is_err());
+
+        apply(&mut text, &history.undo().unwrap());
+        assert_eq!(text, "aXYb!");
+        apply(&mut text, &history.undo().unwrap());
+        assert_eq!(text, "aXYb");
+        apply(&mut text, &history.undo().unwrap());
+        assert_eq!(text, "ab");
+        assert!(history.undo().is_none());
+
+        apply(&mut text, &history.redo().unwrap());
+        apply(&mut text, &history.redo().unwrap());
+        apply(&mut text, &history.redo().unwrap());
+        assert_eq!(text, "AXYBB");
+        assert_eq!(history.redo_len(), 0);
+
+        assert_eq!(merge_changes(
+            &Change { start: 1, removed: String::new(), inserted: "XY".into() },
+            &Change { start: 0, removed: "aX".into(), inserted: String::new() },
+        ), Some(Change { start: 0, removed: "a".into(), inserted: "Y".into() }));
+    }
+
+    #[test]
+    fn groups_typing_with_several_cursors() {
+        let mut history = History::default();
+        let mut text = String::from("ab");
+        let both = |first: (usize, &str), second: (usize, &str)| vec![
+            Change { start: first.0, removed: String::new(), inserted: first.1.into() },
+            Change { start: second.0, removed: String::new(), inserted: second.1.into() },
+        ];
+        history.record(EditKind::Typing, both((0, "x"), (1, "y")), 0);
+        apply(&mut text, &[TextEdit::new(1, 1, "y"), TextEdit::new(0, 0, "x")]);
+        history.record(EditKind::Typing, both((1, "1"), (3, "2")), 100);
+        apply(&mut text, &[TextEdit::new(3, 3, "2"), TextEdit::new(1, 1, "1")]);
+        assert_eq!(text, "x1ay2b");
+        assert_eq!(history.undo_len(), 1);
+
+        apply(&mut text, &history.undo().unwrap());
+        assert_eq!(text, "ab");
+    }
+}
@@ -11,6 +11,12 @@ pub struct IncrementalState {
     pub input_length: usize
 }
 
+impl Default for IncrementalState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IncrementalState {
     pub fn new() -> Self {
         Self {
@@ -1,208 +1,267 @@
 use jni::JNIEnv;
-use jni::objects::{JClass, JObject, JString};
-use jni::sys::{jlong, jstring};
-use tree_sitter::{ffi::TSTree, Parser, Tree};
-use tree_sitter_highlight::{HighlightConfiguration, Highlighter};
-use std::sync::Arc;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use jni::objects::{JByteBuffer, JClass, JObject, JString};
+use jni::sys::{jint, jintArray, jlong, jstring};
+use tree_sitter::{ffi::TSTree, Tree};
 use log::{debug, error};
-use std::time::Instant;
-use tokio::runtime::Builder;
+use std::sync::Arc;
 
+use crate::brackets::{brackets_in_range, match_bracket};
 use crate::code_exec::{execute_code, execute_code_detailed};
-use crate::cache::{get_cached_theme, PARSE_CACHE, PARSER_CACHE, get_highlight_cache, update_highlight_cache};
-use crate::highlighting::{load_language, process_highlights, try_incremental_highlight};
+use crate::compiler_diagnostics::{locate_diagnostics, parse_compiler_output};
+use crate::completion::complete;
+use crate::buffer::TextBuffer;
+use crate::document::{close_document, get_document, open_document, Document};
+use crate::error::{jni_guard, throw_error, JniDefault, RunixError, RunixResult};
+use crate::future::block_on_shared;
+use crate::cache::{all_registered_highlight_names, get_registered_highlight_names, register_highlight_names, LockExt};
+use crate::encoding::{pack_ranges, write_ranges};
+use crate::export::{export_highlighted, ExportFormat, ExportOptions};
+use crate::formatter::{format_document, FormatOptions};
+use crate::highlighting::{apply_highlight_options, highlight_code, DEFAULT_HIGHLIGHT_NAMES};
+use crate::history::{EditKind, History};
+use crate::multi_cursor::{add_next_occurrence, plan_selection_edit, Selection, SelectionEdit};
+use crate::offsets::{OffsetEncoding, TextPoint};
+use crate::query_playground::run_query;
+use crate::search::{find_matches, replace_all, SearchQuery};
+use crate::semantic_tokens::SemanticTokensLegend;
+use crate::snippets::{expand_snippet, load_snippet_pack, parse_snippet, snippet_suggestions, SnippetContext};
+use crate::symbols::document_symbols;
+use crate::syntax_diagnostics::syntax_diagnostics;
+use crate::templates::{index_template_dir, insert_template, list_templates, parse_template, register_template};
+use crate::theme::get_cached_theme;
+use crate::theme_import::{import_theme, ThemeFormat};
+use crate::theme_validation::validate_theme;
+use crate::types::{HighlightOptions, HighlightQueries, HighlightRange, TextEdit};
+
+/// Copia un `JString` a un `String` de Rust sin posibilidad de pánico.
+pub(crate) fn get_rust_string(env: &mut JNIEnv, value: &JString, name: &str) -> RunixResult<String> {
+    let java_str = env.get_string(value)
+        .map_err(|e| RunixError::InvalidArgument(format!("failed to read {}: {}", name, e)))?;
+    java_str.to_str()
+        .map(|s| s.to_owned())
+        .map_err(|e| RunixError::InvalidArgument(format!("{} is not valid UTF-16: {}", name, e)))
+}
+
+/// Convierte el resultado en un `jstring`, o lanza la excepción Java correspondiente y devuelve null.
+pub(crate) fn return_string(env: &mut JNIEnv, result: RunixResult<String>) -> jstring {
+    match result.and_then(|s| env.new_string(s).map_err(RunixError::from)) {
+        Ok(js) => js.into_raw(),
+        Err(e) => {
+            throw_error(env, &e);
+            JObject::null().into_raw()
+        }
+    }
+}
+
+/// Devuelve el valor, o lanza la excepción Java correspondiente y devuelve el valor por defecto.
+pub(crate) fn unwrap_or_throw<T: JniDefault>(env: &mut JNIEnv, result: RunixResult<T>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            throw_error(env, &e);
+            T::jni_default()
+        }
+    }
+}
+
+/// Copia `values` a un `int[]` nuevo.
+pub(crate) fn return_int_array(env: &mut JNIEnv, result: RunixResult<Vec<i32>>) -> jintArray {
+    let array = result.and_then(|values| {
+        let array = env.new_int_array(values.len() as jint)?;
+        env.set_int_array_region(&array, 0, &values)?;
+        Ok(array.into_raw())
+    });
+    unwrap_or_throw(env, array)
+}
+
+pub(crate) fn to_json<T: serde::Serialize>(value: &T, what: &str) -> RunixResult<String> {
+    serde_json::to_string(value)
+        .map_err(|e| RunixError::Jni(format!("failed to serialize {}: {}", what, e)))
+}
+
+// Pasa los offsets de una lista de edits (bytes de `buffer`) a la codificación de Kotlin
+pub(crate) fn convert_edits(buffer: &TextBuffer, edits: &mut [TextEdit], encoding: OffsetEncoding) {
+    for edit in edits {
+        edit.start = buffer.convert(edit.start, encoding);
+        edit.end = buffer.convert(edit.end, encoding);
+    }
+}
+
+// Igual que `convert_edits`, para rangos de resaltado
+fn convert_ranges(buffer: &TextBuffer, ranges: &mut [HighlightRange], encoding: OffsetEncoding) {
+    for range in ranges {
+        range.start = buffer.convert(range.start, encoding);
+        range.end = buffer.convert(range.end, encoding);
+    }
+}
+
+// JSON de opciones opcional: una cadena vacía equivale a las opciones por defecto
+pub(crate) fn parse_options<T: serde::de::DeserializeOwned + Default>(env: &mut JNIEnv, value: &JString, name: &str) -> RunixResult<T> {
+    let json = get_rust_string(env, value, name)?;
+    if json.trim().is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_str(&json).map_err(|e| RunixError::InvalidArgument(format!("{}: {}", name, e)))
+}
+
+fn registered_names(names_id: jint) -> RunixResult<Arc<Vec<String>>> {
+    usize::try_from(names_id).ok()
+        .and_then(get_registered_highlight_names)
+        .ok_or_else(|| RunixError::InvalidArgument(format!("unknown highlight names id {}", names_id)))
+}
+
+fn handle_to_id(handle: jlong) -> RunixResult<u64> {
+    u64::try_from(handle)
+        .map_err(|_| RunixError::InvalidArgument(format!("invalid document handle {}", handle)))
+}
+
+// Offset o cantidad recibida de Kotlin; un valor negativo es un error del llamador
+fn to_offset(value: jint, name: &str) -> RunixResult<usize> {
+    usize::try_from(value)
+        .map_err(|_| RunixError::InvalidArgument(format!("{} must not be negative, got {}", name, value)))
+}
+
+// Rango `[start, end]` en bytes; rechaza los invertidos en lugar de adivinar la intención
+fn byte_range(buffer: &TextBuffer, start: jint, end: jint, encoding: OffsetEncoding) -> RunixResult<std::ops::Range<usize>> {
+    let (start, end) = (to_offset(start, "start")?, to_offset(end, "end")?);
+    if start > end {
+        return Err(RunixError::InvalidArgument(format!("start {} is after end {}", start, end)));
+    }
+    Ok(buffer.to_byte(start, encoding)..buffer.to_byte(end, encoding))
+}
 
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_helloRust(
-    env: JNIEnv,
+    mut env: JNIEnv,
     _class: JClass,
 ) -> jstring {
-    let hello = "Hello from Rust!";
-    let output = env.new_string(hello).unwrap();
-    output.into_raw()
+    jni_guard(&mut env, |env| {
+        let hello = "Hello from Rust!";
+        return_string(env, Ok(hello.to_string()))
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_freeTree(
-    _env: JNIEnv,
+    mut env: JNIEnv,
     _class: JClass,
     tree_ptr: jlong,
 ) {
-    if tree_ptr != 0 {
-        // Reconstitute the tree from the raw pointer and let it be dropped.
-        // This will call its `delete` method if `Tree` implements `Drop` appropriately.
-        // tree_sitter::Tree does implement Drop and calls ffi::ts_tree_delete.
-        let _ = unsafe { Tree::from_raw(tree_ptr as *mut TSTree) };
-    }
+    jni_guard(&mut env, |_| {
+        if tree_ptr != 0 {
+            // Reconstitute the tree from the raw pointer and let it be dropped.
+            // This will call its `delete` method if `Tree` implements `Drop` appropriately.
+            // tree_sitter::Tree does implement Drop and calls ffi::ts_tree_delete.
+            let _ = unsafe { Tree::from_raw(tree_ptr as *mut TSTree) };
+        }
+    })
 }
 
-#[unsafe(no_mangle)]
-pub unsafe extern "system" fn Java_com_example_lancelot_rust_RustBridge_highlight(
-    mut env: JNIEnv,
-    _class: JClass, 
-    code: JString,
-    language_name_jstr: JString,
-    highlights_scm_jstr: JString,
-    injections_scm_jstr: JString,
-    locals_scm_jstr: JString,
-    theme_json_jstr: JString,
-    highlight_names_json_jstr: JString
-) -> jstring {
+#[allow(clippy::too_many_arguments)]
+fn highlight_impl(
+    env: &mut JNIEnv,
+    code: &JString,
+    language_name_jstr: &JString,
+    highlights_scm_jstr: &JString,
+    injections_scm_jstr: &JString,
+    locals_scm_jstr: &JString,
+    theme_json_jstr: &JString,
+    highlight_names_json_jstr: &JString,
+    options_json_jstr: Option<&JString>
+) -> RunixResult<String> {
     debug!("tokenizeCode: Starting code tokenization");
-    
-    let input = match env.get_string(&code) {
-        Ok(js) => match js.to_str() {
-            Ok(s) => s.to_string(),
-            Err(e) => {
-                error!("Error converting code string: {:?}", e);
-                return JObject::null().into_raw();
-            }
-        },
-        Err(e) => {            
-            error!("Error getting code string from JNI: {:?}", e);
-            return JObject::null().into_raw();
-        }
-    };
 
-    let language_name = match env.get_string(&language_name_jstr) {
-        Ok(js) => match js.to_str() {
-            Ok(s) => s.to_string(),
-            Err(e) => {
-                error!("Error converting language_name string: {:?}", e);
-                return JObject::null().into_raw();
-            }
-        },
-        Err(e) => {            
-            error!("Error getting language_name string from JNI: {:?}", e);
-            return JObject::null().into_raw();
-        }
+    let options: HighlightOptions = match options_json_jstr {
+        Some(jstr) => parse_options(env, jstr, "options_json")?,
+        None => HighlightOptions::default(),
     };
 
-    let mut hasher = DefaultHasher::new();
-    input.hash(&mut hasher);
-    language_name.hash(&mut hasher);
-    let input_hash = hasher.finish();
+    let input = get_rust_string(env, code, "code")?;
+    let language_name = get_rust_string(env, language_name_jstr, "language_name")?;
 
-    let highlight_names: Vec<String> = env.get_string(&highlight_names_json_jstr)
-        .map_err(|e| error!("Failed to get highlight_names_json: {:?}", e))
+    let highlight_names: Vec<String> = get_rust_string(env, highlight_names_json_jstr, "highlight_names_json")
         .ok()
-        .and_then(|s| s.to_str().map(|s_str| s_str.to_owned()).map_err(|e| error!("Failed to convert highlight_names_json to str: {:?}",e)).ok())
-        .and_then(|s_owned| serde_json::from_str::<Vec<String>>(&s_owned).map_err(|e| error!("Failed to parse highlight_names_json: {:?}",e)).ok())
-        .unwrap_or_else(|| vec![
-            "keyword".to_string(), "function".to_string(), "type".to_string(),
-            "string".to_string(), "number".to_string(), "comment".to_string(),
-            "constant".to_string(), "variable".to_string(),
-        ]);
-
-    if let Some(cache) = get_highlight_cache(&language_name) {
-        if cache.matches_input(&input) {
-            if let Some(delta) = try_incremental_highlight(&*cache, &input, &highlight_names) {
-                return match serde_json::to_string(&delta) {
-                    Ok(json) => env.new_string(&json).unwrap().into_raw(),
-                    Err(e) => {
-                        error!("Error serializing cached result: {:?}", e);
-                        JObject::null().into_raw()
-                    }
-                };
-            }
-        }
-    }
+        .and_then(|s| serde_json::from_str::<Vec<String>>(&s).map_err(|e| error!("Failed to parse highlight_names_json: {:?}", e)).ok())
+        .unwrap_or_else(|| DEFAULT_HIGHLIGHT_NAMES.iter().map(|s| s.to_string()).collect());
 
-    let highlights_scm = match env.get_string(&highlights_scm_jstr).map(|s| s.to_str().unwrap().to_string()) {
-        Ok(s) => s, Err(_) => { error!("Failed to get highlights_scm"); return JObject::null().into_raw(); }
-    };
-    let injections_scm = match env.get_string(&injections_scm_jstr).map(|s| s.to_str().unwrap().to_string()) {
-        Ok(s) => s, Err(_) => { error!("Failed to get injections_scm"); return JObject::null().into_raw(); }
-    };
-    let locals_scm = match env.get_string(&locals_scm_jstr).map(|s| s.to_str().unwrap().to_string()) {
-        Ok(s) => s, Err(_) => { error!("Failed to get locals_scm"); return JObject::null().into_raw(); }
-    };
-    let theme_json = match env.get_string(&theme_json_jstr).map(|s| s.to_str().unwrap().to_string()) {
-        Ok(s) => s, Err(_) => { error!("Failed to get theme_json"); return JObject::null().into_raw(); }
-    };
+    let highlights_scm = get_rust_string(env, highlights_scm_jstr, "highlights_scm")?;
+    let injections_scm = get_rust_string(env, injections_scm_jstr, "injections_scm")?;
+    let locals_scm = get_rust_string(env, locals_scm_jstr, "locals_scm")?;
+    let theme_json = get_rust_string(env, theme_json_jstr, "theme_json")?;
 
-    let language = match load_language(&language_name) {
-        Ok(l) => l,
-        Err(e) => {
-            error!("Error loading language '{}': {:?}", language_name, e);
-            return JObject::null().into_raw();
-        }
-    };
-
-    let mut highlighter = Highlighter::new();
-    let mut config = match HighlightConfiguration::new(
-        language.clone(),
-        "highlighter", // Name for the configuration, can be arbitrary
-        &highlights_scm,
-        &injections_scm,
-        &locals_scm,
-    ) {
-        Ok(c) => c,
-        Err(e) => {
-            error!("Error creating highlighter config: {:?}", e);
-            return JObject::null().into_raw();
-        }
-    };
-
-    let highlight_name_refs: Vec<&str> = highlight_names.iter().map(|s| s.as_str()).collect();
-    config.configure(&highlight_name_refs);
-
-    let old_tree_arc_opt = PARSE_CACHE.lock().unwrap()
-        .get(&input_hash)
-        .filter(|(_, cached_input)| cached_input == &input)
-        .map(|(tree, _)| Arc::clone(tree));
-
-    let new_tree = {
-        let mut parser_cache_guard = PARSER_CACHE.lock().unwrap();
-        let parser = parser_cache_guard.entry(language_name.clone()).or_insert_with(|| {
-            debug!("Creating new parser for {}", language_name);
-            let mut p = Parser::new();
-            p.set_language(&language).expect("Failed to set language on new parser");
-            p
-        });
-        
-        match parser.parse(&input, old_tree_arc_opt.as_ref().map(|t| &**t)) {
-            Some(tree) => Arc::new(tree),
-            None => {
-                error!("Parsing failed for language {} with input snippet: {:.50}", language_name, input);
-                return JObject::null().into_raw();
-            }
-        }
-    };
-
-    let highlights_iter = match highlighter.highlight(
-        &config,
-        input.as_bytes(),
-        None, // No injection callback needed for this basic setup
-        |_| None // No injection callback
-    ) {
-        Ok(h) => h,
-        Err(e) => {
-            error!("Error highlighting code: {:?}", e);
-            return JObject::null().into_raw();
-        }
+    let queries = HighlightQueries {
+        highlights: &highlights_scm,
+        injections: &injections_scm,
+        locals: &locals_scm,
     };
+    let mut highlight_delta = highlight_code(&input, &language_name, &queries, &highlight_names)?;
+    let theme = get_cached_theme(&theme_json);
+    apply_highlight_options(&mut highlight_delta, &input, &options, &theme);
 
-    let _theme_arc = get_cached_theme(&theme_json); // Theme is cached, but not directly used in process_highlights in this version
-    let (highlight_delta, ranges) = process_highlights(highlights_iter, highlight_names.clone(), Arc::clone(&new_tree), &input);
+    to_json(&highlight_delta, "highlight result")
+}
 
-    let start_time = Instant::now();
-    update_highlight_cache(language_name.clone(), input_hash, Arc::clone(&new_tree), input.clone(), ranges, highlight_delta.clone());
-    debug!("Cache update completed in {:?}", start_time.elapsed());
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_highlight(
+    mut env: JNIEnv,
+    _class: JClass, 
+    code: JString,
+    language_name_jstr: JString,
+    highlights_scm_jstr: JString,
+    injections_scm_jstr: JString,
+    locals_scm_jstr: JString,
+    theme_json_jstr: JString,
+    highlight_names_json_jstr: JString
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = highlight_impl(
+            env,
+            &code,
+            &language_name_jstr,
+            &highlights_scm_jstr,
+            &injections_scm_jstr,
+            &locals_scm_jstr,
+            &theme_json_jstr,
+            &highlight_names_json_jstr,
+            None,
+        );
+        return_string(env, result)
+    })
+}
 
-    match serde_json::to_string(&highlight_delta) {
-        Ok(json) => env.new_string(&json).unwrap().into_raw(),
-        Err(e) => {
-            error!("Error serializing result: {:?}", e);
-            JObject::null().into_raw()
-        }
-    }
+/// Igual que `highlight`, con `options_json` (ver `HighlightOptions`) para pedir offsets
+/// UTF-16 y puntos fila/columna.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_highlightWithOptions(
+    mut env: JNIEnv,
+    _class: JClass,
+    code: JString,
+    language_name_jstr: JString,
+    highlights_scm_jstr: JString,
+    injections_scm_jstr: JString,
+    locals_scm_jstr: JString,
+    theme_json_jstr: JString,
+    highlight_names_json_jstr: JString,
+    options_json_jstr: JString
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = highlight_impl(
+            env,
+            &code,
+            &language_name_jstr,
+            &highlights_scm_jstr,
+            &injections_scm_jstr,
+            &locals_scm_jstr,
+            &theme_json_jstr,
+            &highlight_names_json_jstr,
+            Some(&options_json_jstr),
+        );
+        return_string(env, result)
+    })
 }
 
 #[unsafe(no_mangle)]
-pub unsafe extern "system" fn Java_com_example_lancelot_rust_RustBridge_realTimeHighlight(
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_realTimeHighlight(
     env: JNIEnv,
     class: JClass,
     code: JString,
@@ -227,6 +286,766 @@ pub unsafe extern "system" fn Java_com_example_lancelot_rust_RustBridge_realTime
     )
 }
 
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_registerHighlightNames(
+    mut env: JNIEnv,
+    _class: JClass,
+    highlight_names_json_jstr: JString,
+) -> jint {
+    jni_guard(&mut env, |env| {
+        let result = get_rust_string(env, &highlight_names_json_jstr, "highlight_names_json")
+            .and_then(|json| serde_json::from_str::<Vec<String>>(&json)
+                .map_err(|e| RunixError::InvalidArgument(format!("highlight_names_json: {}", e))));
+
+        match result {
+            Ok(names) => register_highlight_names(names) as jint,
+            Err(e) => {
+                throw_error(env, &e);
+                -1
+            }
+        }
+    })
+}
+
+// Resaltado completo de un documento abierto con offsets en `offset_encoding`. Reutiliza su
+// árbol y sus queries compiladas, así que no hay que copiar el código ni los .scm
+fn highlight_document(handle: jlong, offset_encoding: jint) -> RunixResult<Vec<HighlightRange>> {
+    let document = get_document(handle_to_id(handle)?)?;
+    let document = document.lock_or_recover();
+    let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+    let mut ranges = document.highlight_range(0..document.buffer().len());
+    convert_ranges(document.buffer(), &mut ranges, encoding);
+    Ok(ranges)
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_highlightPacked(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset_encoding: jint,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = highlight_document(handle, offset_encoding).map(|ranges| pack_ranges(&ranges));
+        return_int_array(env, result)
+    })
+}
+
+/// Escribe los rangos en un `ByteBuffer` directo (orden nativo). Devuelve el número de
+/// rangos escritos, o `-n` si el buffer no tiene espacio para los `n` rangos del resultado.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_highlightIntoBuffer(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset_encoding: jint,
+    buffer: JByteBuffer,
+) -> jint {
+    jni_guard(&mut env, |env| {
+        let result = highlight_document(handle, offset_encoding).and_then(|ranges| {
+            let address = env.get_direct_buffer_address(&buffer)?;
+            let capacity = env.get_direct_buffer_capacity(&buffer)?;
+            // SAFETY: la JVM garantiza que el buffer directo es válido durante la llamada
+            let bytes = unsafe { std::slice::from_raw_parts_mut(address, capacity) };
+            Ok(match write_ranges(&ranges, bytes) {
+                Some(written) => written as jint,
+                None => -(ranges.len() as jint),
+            })
+        });
+
+        unwrap_or_throw(env, result)
+    })
+}
+
+/// Estilo resuelto de cada nombre registrado como tripletas `(foreground, background, flags)`
+/// en ARGB. Un color sólo está definido si `flags` lleva `HAS_FOREGROUND`/`HAS_BACKGROUND`;
+/// sin ellos el slot vale `0`. Sirve para colorear las salidas empaquetadas sin repetir la
+/// resolución en Kotlin.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_resolveThemeStyles(
+    mut env: JNIEnv,
+    _class: JClass,
+    theme_json_jstr: JString,
+    names_id: jint,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let highlight_names = registered_names(names_id)?;
+            let theme = get_cached_theme(&get_rust_string(env, &theme_json_jstr, "theme_json")?);
+            let table = theme.resolve_styles(&highlight_names);
+
+            let mut packed = Vec::with_capacity(highlight_names.len() * 3);
+            for highlight_type in 0..highlight_names.len() {
+                let style = table.style_for_highlight(highlight_type).copied().unwrap_or_default();
+                packed.push(style.foreground.unwrap_or(0) as i32);
+                packed.push(style.background.unwrap_or(0) as i32);
+                packed.push(style.flags());
+            }
+            Ok(packed)
+        })();
+        return_int_array(env, result)
+    })
+}
+
+/// Lista JSON de problemas del tema (sintaxis, colores, capturas desconocidas, colores base).
+/// Las capturas se comparan con los nombres registrados y los nombres por defecto.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_validateTheme(
+    mut env: JNIEnv,
+    _class: JClass,
+    theme_json_jstr: JString,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let theme_json = get_rust_string(env, &theme_json_jstr, "theme_json")?;
+            let mut known_names = all_registered_highlight_names();
+            known_names.extend(DEFAULT_HIGHLIGHT_NAMES.iter().map(|s| s.to_string()));
+            let problems = validate_theme(&theme_json, &known_names);
+            to_json(&problems, "theme problems")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Convierte un tema de VS Code, TextMate o Helix al JSON de `Theme`, con los scopes que no
+/// tienen captura equivalente en `unknown_scopes`. `format` vacío lo deduce del contenido.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_importTheme(
+    mut env: JNIEnv,
+    _class: JClass,
+    source_jstr: JString,
+    format_jstr: JString,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let source = get_rust_string(env, &source_jstr, "source")?;
+            let format_name = get_rust_string(env, &format_jstr, "format")?;
+            let format = match format_name.trim() {
+                "" => None,
+                name => Some(ThemeFormat::parse(name)
+                    .ok_or_else(|| RunixError::InvalidArgument(format!("unknown theme format '{}'", name)))?),
+            };
+            let imported = import_theme(&source, format)?;
+            to_json(&imported, "imported theme")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Abre un documento nativo y devuelve su handle. El árbol queda cacheado hasta `closeDocument`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_openDocument(
+    mut env: JNIEnv,
+    _class: JClass,
+    language_name_jstr: JString,
+    code: JString,
+    highlights_scm_jstr: JString,
+    injections_scm_jstr: JString,
+    locals_scm_jstr: JString,
+    names_id: jint,
+) -> jlong {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let highlight_names = registered_names(names_id)?;
+            let language_name = get_rust_string(env, &language_name_jstr, "language_name")?;
+            let input = get_rust_string(env, &code, "code")?;
+            let highlights_scm = get_rust_string(env, &highlights_scm_jstr, "highlights_scm")?;
+            let injections_scm = get_rust_string(env, &injections_scm_jstr, "injections_scm")?;
+            let locals_scm = get_rust_string(env, &locals_scm_jstr, "locals_scm")?;
+
+            let queries = HighlightQueries {
+                highlights: &highlights_scm,
+                injections: &injections_scm,
+                locals: &locals_scm,
+            };
+            let document = Document::new(&language_name, input, &queries, highlight_names)?;
+            Ok(open_document(document) as jlong)
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_updateDocument(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    code: JString,
+) {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let input = get_rust_string(env, &code, "code")?;
+            let result = document.lock_or_recover().set_text(input);
+            result
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+/// Reemplaza `[start, end)` por `text` sin mandar el documento completo; devuelve la nueva
+/// versión. Offsets según `offsetEncoding`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_editDocument(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    start: jint,
+    end: jint,
+    text_jstr: JString,
+    offset_encoding: jint,
+) -> jlong {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let text = get_rust_string(env, &text_jstr, "text")?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let mut document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let range = byte_range(document.buffer(), start, end, encoding)?;
+            document.apply_edits(&[TextEdit::new(range.start, range.end, text)], None)?;
+            Ok(document.version as jlong)
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+/// Aplica una lista JSON de edits no solapados (start, end, text), con offsets del texto
+/// actual según `offsetEncoding`, y reparsea una sola vez. Devuelve la nueva versión.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_applyEdits(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    edits_json_jstr: JString,
+    offset_encoding: jint,
+) -> jlong {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let json = get_rust_string(env, &edits_json_jstr, "edits_json")?;
+            let mut edits: Vec<TextEdit> = serde_json::from_str(&json)
+                .map_err(|e| RunixError::InvalidArgument(format!("invalid edits_json: {}", e)))?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let mut document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            for edit in &mut edits {
+                edit.start = document.buffer().to_byte(edit.start, encoding);
+                edit.end = document.buffer().to_byte(edit.end, encoding);
+            }
+            document.apply_edits(&edits, None)?;
+            Ok(document.version as jlong)
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+/// Posición `[line, column]` (0-based, columna en `offsetEncoding`) de un offset.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_positionAt(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset: jint,
+    offset_encoding: jint,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let buffer = document.buffer();
+            let point = buffer.byte_to_point(buffer.to_byte(to_offset(offset, "offset")?, encoding), encoding);
+            Ok(vec![point.row as i32, point.column as i32])
+        })();
+        return_int_array(env, result)
+    })
+}
+
+/// Offset de `line`/`column` (0-based, columna en `offsetEncoding`), limitado al final de la línea.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_offsetAt(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    line: jint,
+    column: jint,
+    offset_encoding: jint,
+) -> jint {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let point = TextPoint { row: to_offset(line, "line")?, column: to_offset(column, "column")? };
+            let buffer = document.buffer();
+            Ok(buffer.convert(buffer.point_to_byte(point, encoding), encoding) as jint)
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+/// Texto actual del documento, para cuando Kotlin necesita resincronizarse.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_documentText(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = handle_to_id(handle)
+            .and_then(get_document)
+            .map(|document| document.lock_or_recover().text().to_string());
+        return_string(env, result)
+    })
+}
+
+/// Deshace la última transacción del historial nativo. Devuelve en JSON los edits aplicados
+/// (start, end, text) en orden, cada uno sobre el texto que deja el anterior; `[]` si no hay
+/// nada que deshacer.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_undo(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let edits = document.lock_or_recover().undo(OffsetEncoding::from_jint(offset_encoding)?)?;
+            to_json(&edits, "undo edits")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Rehace la última transacción deshecha; mismo formato que `undo`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_redo(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let edits = document.lock_or_recover().redo(OffsetEncoding::from_jint(offset_encoding)?)?;
+            to_json(&edits, "redo edits")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Agrupa las ediciones siguientes en una transacción de tipo `kind` (typing, delete, paste,
+/// snippet, format, other) hasta `endUndoGroup`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_beginUndoGroup(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    kind_jstr: JString,
+) {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let kind_name = get_rust_string(env, &kind_jstr, "kind")?;
+            let kind = EditKind::parse(&kind_name)
+                .ok_or_else(|| RunixError::InvalidArgument(format!("unknown edit kind: {}", kind_name)))?;
+            let document = get_document(handle_to_id(handle)?)?;
+            document.lock_or_recover().begin_undo_group(kind);
+            Ok(())
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_endUndoGroup(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) {
+    jni_guard(&mut env, |env| {
+        let result = handle_to_id(handle)
+            .and_then(get_document)
+            .map(|document| document.lock_or_recover().history().end_group());
+        unwrap_or_throw(env, result)
+    })
+}
+
+/// Impide que la próxima pulsación se agrupe con la anterior (p. ej. tras mover el cursor).
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_breakUndoGroup(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) {
+    jni_guard(&mut env, |env| {
+        let result = handle_to_id(handle)
+            .and_then(get_document)
+            .map(|document| document.lock_or_recover().history().seal());
+        unwrap_or_throw(env, result)
+    })
+}
+
+/// `[undoCount, redoCount]` para habilitar los botones de deshacer/rehacer.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_historyState(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = handle_to_id(handle).and_then(get_document).map(|document| {
+            let mut document = document.lock_or_recover();
+            let history = document.history();
+            vec![history.undo_len() as i32, history.redo_len() as i32]
+        });
+        return_int_array(env, result)
+    })
+}
+
+/// Historial serializado para guardarlo junto al archivo y restaurarlo al reabrirlo.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_saveHistory(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let mut document = document.lock_or_recover();
+            let text = document.text().to_string();
+            document.history().save(&text)
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Restaura un historial de `saveHistory`; lanza una excepción si el texto del documento ya
+/// no es el que tenía al guardarlo.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_restoreHistory(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    history_json_jstr: JString,
+) {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let json = get_rust_string(env, &history_json_jstr, "history_json")?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let mut document = document.lock_or_recover();
+            let history = History::restore(&json, document.text())?;
+            *document.history() = history;
+            Ok(())
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+// Selecciones JSON `[{anchor, head}]` de Kotlin a bytes del buffer, y de vuelta
+fn parse_selections(env: &mut JNIEnv, value: &JString, buffer: &TextBuffer, encoding: OffsetEncoding) -> RunixResult<Vec<Selection>> {
+    let json = get_rust_string(env, value, "selections_json")?;
+    let selections: Vec<Selection> = serde_json::from_str(&json)
+        .map_err(|e| RunixError::InvalidArgument(format!("invalid selections_json: {}", e)))?;
+    Ok(selections.into_iter()
+        .map(|s| Selection { anchor: buffer.to_byte(s.anchor, encoding), head: buffer.to_byte(s.head, encoding) })
+        .collect())
+}
+
+fn convert_selections(buffer: &TextBuffer, selections: &mut [Selection], encoding: OffsetEncoding) {
+    for selection in selections {
+        selection.anchor = buffer.convert(selection.anchor, encoding);
+        selection.head = buffer.convert(selection.head, encoding);
+    }
+}
+
+/// Aplica `editJson` (`{"type": "insert"|"replace", "text": ...}` o `{"type":
+/// "delete_backward"|"delete_forward"}`) en todas las selecciones a la vez, con una sola
+/// transacción de deshacer y un solo reparseo. Devuelve `edits` (sobre el texto anterior) y
+/// las nuevas `selections`, con offsets según `offsetEncoding`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_editSelections(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    selections_json_jstr: JString,
+    edit_json_jstr: JString,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let edit_json = get_rust_string(env, &edit_json_jstr, "edit_json")?;
+            let edit: SelectionEdit = serde_json::from_str(&edit_json)
+                .map_err(|e| RunixError::InvalidArgument(format!("invalid edit_json: {}", e)))?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let mut document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let selections = parse_selections(env, &selections_json_jstr, document.buffer(), encoding)?;
+
+            let mut planned = plan_selection_edit(document.buffer(), &selections, &edit);
+            let kind = planned.kind(document.buffer());
+            // Clonar el rope es O(1); los edits se convierten sobre el texto anterior
+            let before = document.buffer().clone();
+            document.apply_edits(&planned.edits, Some(kind))?;
+            for text_edit in &mut planned.edits {
+                text_edit.start = before.convert(text_edit.start, encoding);
+                text_edit.end = before.convert(text_edit.end, encoding);
+            }
+            convert_selections(document.buffer(), &mut planned.selections, encoding);
+            to_json(&planned, "selection edit")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Añade a `selectionsJson` la siguiente aparición del texto de la última selección (o
+/// amplía el cursor a su palabra). Devuelve las selecciones en JSON.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_addNextOccurrence(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    selections_json_jstr: JString,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let selections = parse_selections(env, &selections_json_jstr, document.buffer(), encoding)?;
+            let mut selections = add_next_occurrence(document.text(), &selections);
+            convert_selections(document.buffer(), &mut selections, encoding);
+            to_json(&selections, "selections")
+        })();
+        return_string(env, result)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_closeDocument(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) {
+    jni_guard(&mut env, |_| {
+        if let Ok(id) = handle_to_id(handle) {
+            close_document(id);
+        }
+    })
+}
+
+/// Tripletas (start, end, type) sólo para los rangos que intersecan `[start, end)`; los
+/// offsets de entrada y salida van en `offset_encoding`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_highlightRange(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    start: jint,
+    end: jint,
+    offset_encoding: jint,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let range = byte_range(document.buffer(), start, end, encoding)?;
+            let mut ranges = document.highlight_range(range);
+            convert_ranges(document.buffer(), &mut ranges, encoding);
+            Ok(pack_ranges(&ranges))
+        })();
+        return_int_array(env, result)
+    })
+}
+
+/// Documento completo coloreado con el tema en `html`, `ansi` o `rtf`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_exportHighlighted(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    format_jstr: JString,
+    theme_json_jstr: JString,
+    options_json_jstr: JString,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let format_name = get_rust_string(env, &format_jstr, "format")?;
+            let format = ExportFormat::parse(format_name.trim())
+                .ok_or_else(|| RunixError::InvalidArgument(format!("unknown export format '{}'", format_name)))?;
+            let theme = get_cached_theme(&get_rust_string(env, &theme_json_jstr, "theme_json")?);
+            let options: ExportOptions = parse_options(env, &options_json_jstr, "options_json")?;
+
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let ranges = document.highlight_range(0..document.buffer().len());
+            Ok(export_highlighted(document.text(), &ranges, &document.highlight_names, &theme, format, &options))
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Errores de sintaxis (nodos `ERROR` y `MISSING`) del árbol cacheado, en JSON.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_syntaxDiagnostics(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let diagnostics = syntax_diagnostics(&document.tree, document.buffer(), OffsetEncoding::from_jint(offset_encoding)?);
+            to_json(&diagnostics, "syntax diagnostics")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Outline jerárquico (funciones, clases, structs, métodos, variables globales) en JSON.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_documentSymbols(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let symbols = document_symbols(
+                &document.tree,
+                document.buffer(),
+                &document.language_name,
+                &document.language,
+                OffsetEncoding::from_jint(offset_encoding)?,
+            )?;
+            to_json(&symbols, "document symbols")
+        })();
+        return_string(env, result)
+    })
+}
+
+// Ejecuta `update` sobre la selección del documento traducida a bytes y devuelve [start, end]
+fn select_with(
+    handle: jlong,
+    start: jint,
+    end: jint,
+    offset_encoding: jint,
+    update: impl FnOnce(&mut Document, std::ops::Range<usize>) -> std::ops::Range<usize>,
+) -> RunixResult<Vec<i32>> {
+    let document = get_document(handle_to_id(handle)?)?;
+    let mut document = document.lock_or_recover();
+    let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+    let range = byte_range(document.buffer(), start, end, encoding)?;
+    let selection = update(&mut document, range);
+    let buffer = document.buffer();
+    Ok(vec![
+        buffer.convert(selection.start, encoding) as i32,
+        buffer.convert(selection.end, encoding) as i32,
+    ])
+}
+
+/// Nueva selección `[start, end]` ampliada al nodo con nombre que contiene la actual.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_expandSelection(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    start: jint,
+    end: jint,
+    offset_encoding: jint,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = select_with(handle, start, end, offset_encoding, Document::expand_selection);
+        return_int_array(env, result)
+    })
+}
+
+/// Nueva selección `[start, end]` reducida: deshace la última ampliación o baja a un nodo hijo.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_shrinkSelection(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    start: jint,
+    end: jint,
+    offset_encoding: jint,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = select_with(handle, start, end, offset_encoding, Document::shrink_selection);
+        return_int_array(env, result)
+    })
+}
+
+/// Pareja del corchete en `offset` (o justo antes) como `[openStart, openEnd, closeStart, closeEnd]`;
+/// array vacío si no hay corchete o está desemparejado.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_matchBracket(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset: jint,
+    offset_encoding: jint,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let buffer = document.buffer();
+            let offset = buffer.to_byte(to_offset(offset, "offset")?, encoding);
+            Ok(match match_bracket(&document.tree, offset) {
+                Some(pair) => [pair.open.start, pair.open.end, pair.close.start, pair.close.end]
+                    .iter()
+                    .map(|&byte| buffer.convert(byte, encoding) as i32)
+                    .collect(),
+                None => Vec::new(),
+            })
+        })();
+        return_int_array(env, result)
+    })
+}
+
+/// Tripletas `(start, end, depth)` de los corchetes en `[start, end)`; `depth` es `-1` para
+/// los desemparejados. Los de cadenas y comentarios no aparecen.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_bracketDepths(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    start: jint,
+    end: jint,
+    offset_encoding: jint,
+) -> jintArray {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let buffer = document.buffer();
+            let range = byte_range(buffer, start, end, encoding)?;
+
+            let brackets = brackets_in_range(&document.tree, range);
+            let mut packed = Vec::with_capacity(brackets.len() * 3);
+            for bracket in brackets {
+                packed.push(buffer.convert(bracket.range.start, encoding) as i32);
+                packed.push(buffer.convert(bracket.range.end, encoding) as i32);
+                packed.push(if bracket.matched { bracket.depth as i32 } else { -1 });
+            }
+            Ok(packed)
+        })();
+        return_int_array(env, result)
+    })
+}
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_executeCode(
     mut env: JNIEnv,
@@ -235,23 +1054,20 @@ pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_executeCode(
     language_name: JString,
     input: JString,
 ) -> jstring {
-    let code: String = env.get_string(&code).unwrap().into();
-    let language_name: String = env.get_string(&language_name).unwrap().into();
-    let input: String = env.get_string(&input).unwrap().into();
-
-    // Creamos un runtime local para ejecutar async
-    let rt = Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .unwrap();
-    let result = rt.block_on(async {
-        execute_code(&code, &language_name, &input).await
-    });
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let code = get_rust_string(env, &code, "code")?;
+            let language_name = get_rust_string(env, &language_name, "language_name")?;
+            let input = get_rust_string(env, &input, "input")?;
 
-    // Convertimos resultado a jstring para devolver a Java
-    let output = result.unwrap_or_else(|err| err);
+            // Misma tarea que executeCodeAsync, esperada desde el hilo JNI
+            block_on_shared(async move {
+                execute_code(&code, &language_name, &input).await
+            })?
+        })();
 
-    env.new_string(output).unwrap().into_raw()
+        return_string(env, result)
+    })
 }
 
 #[unsafe(no_mangle)]
@@ -262,22 +1078,392 @@ pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_executeCodeDeta
     language_name: JString,
     input: JString,
 ) -> jstring {
-    let code: String = env.get_string(&code).unwrap().into();
-    let language_name: String = env.get_string(&language_name).unwrap().into();
-    let input: String = env.get_string(&input).unwrap().into();
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let code = get_rust_string(env, &code, "code")?;
+            let language_name = get_rust_string(env, &language_name, "language_name")?;
+            let input = get_rust_string(env, &input, "input")?;
 
-    let rt = Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .unwrap();
-    let result = rt.block_on(async {
-        execute_code_detailed(&code, &language_name, &input).await
-    });
+            let res = block_on_shared(async move {
+                execute_code_detailed(&code, &language_name, &input).await
+            })??;
+            to_json(&res, "execution result")
+        })();
 
-    let output = match result {
-        Ok(res) => serde_json::to_string(&res).unwrap_or_else(|_| "{}".into()),
-        Err(err) => format!("{{\"error\":\"{}\"}}", err.replace('"', "'")),
-    };
+        return_string(env, result)
+    })
+}
+
+/// Leyenda LSP (`tokenTypes`, `tokenModifiers`) para una lista de nombres registrada.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_semanticTokensLegend(
+    mut env: JNIEnv,
+    _class: JClass,
+    names_id: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = registered_names(names_id)
+            .and_then(|names| to_json(&SemanticTokensLegend::new(&names), "semantic tokens legend"));
+        return_string(env, result)
+    })
+}
+
+/// `SemanticTokens` de LSP (`resultId`, `data`) para el documento completo.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_semanticTokens(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let tokens = document.lock_or_recover().semantic_tokens();
+            to_json(&tokens, "semantic tokens")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// `SemanticTokensDelta` respecto a `previousResultId`, o tokens completos si ya no aplica.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_semanticTokensDelta(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    previous_result_id_jstr: JString,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let previous_result_id = get_rust_string(env, &previous_result_id_jstr, "previous_result_id")?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let delta = document.lock_or_recover().semantic_tokens_delta(&previous_result_id);
+            to_json(&delta, "semantic tokens delta")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Completados para el cursor en JSON: `prefix_start`, `prefix_end` y `items` ordenados
+/// (label, kind, detail, score). Offsets según `offsetEncoding`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_completions(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    offset: jint,
+    offset_encoding: jint,
+    limit: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let cursor = document.buffer().to_byte(to_offset(offset, "offset")?, encoding);
+
+            let mut list = complete(&document, cursor, to_offset(limit, "limit")?);
+            list.prefix_start = document.buffer().convert(list.prefix_start, encoding);
+            list.prefix_end = document.buffer().convert(list.prefix_end, encoding);
+            to_json(&list, "completions")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Carga un paquete de snippets en formato VS Code; devuelve cuántos se han cargado.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_loadSnippetPack(
+    mut env: JNIEnv,
+    _class: JClass,
+    language_jstr: JString,
+    pack_json_jstr: JString,
+) -> jint {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let language = get_rust_string(env, &language_jstr, "language")?;
+            let pack_json = get_rust_string(env, &pack_json_jstr, "pack_json")?;
+            let count = load_snippet_pack(&language, &pack_json)?;
+            Ok(count as jint)
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+/// Snippets cargados cuyo prefijo encaja (difuso) con `prefix`, en JSON.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_snippetSuggestions(
+    mut env: JNIEnv,
+    _class: JClass,
+    language_jstr: JString,
+    prefix_jstr: JString,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let language = get_rust_string(env, &language_jstr, "language")?;
+            let prefix = get_rust_string(env, &prefix_jstr, "prefix")?;
+            to_json(&snippet_suggestions(&language, &prefix), "snippet suggestions")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Expande el cuerpo de un snippet: `text` y `tab_stops` (index, ranges, choices) en el orden
+/// de recorrido, con rangos relativos al texto insertado según `offsetEncoding`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_expandSnippet(
+    mut env: JNIEnv,
+    _class: JClass,
+    body_jstr: JString,
+    context_json_jstr: JString,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let body = get_rust_string(env, &body_jstr, "body")?;
+            let context: SnippetContext = parse_options(env, &context_json_jstr, "context_json")?;
+            let mut expanded = expand_snippet(&parse_snippet(&body), &context);
+
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let buffer = TextBuffer::new(&expanded.text);
+            for tab_stop in &mut expanded.tab_stops {
+                for range in &mut tab_stop.ranges {
+                    *range = (buffer.convert(range.0, encoding), buffer.convert(range.1, encoding));
+                }
+            }
+            to_json(&expanded, "expanded snippet")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Diagnósticos estructurados (file, line, column, severity, message, notes) de la salida
+/// de un compilador o de un traceback de Python. Líneas y columnas 1-based; la columna se
+/// interpreta en bytes UTF-8.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_parseCompilerDiagnostics(
+    mut env: JNIEnv,
+    _class: JClass,
+    output_jstr: JString,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = get_rust_string(env, &output_jstr, "output")
+            .and_then(|output| to_json(&parse_compiler_output(&output), "compiler diagnostics"));
+        return_string(env, result)
+    })
+}
+
+/// Igual que `parseCompilerDiagnostics`, añadiendo `offset` (en `offset_encoding`) a los
+/// diagnósticos de `file_name`, el nombre con el que se compiló el documento abierto.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_parseDocumentDiagnostics(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    output_jstr: JString,
+    file_name_jstr: JString,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let output = get_rust_string(env, &output_jstr, "output")?;
+            let file_name = get_rust_string(env, &file_name_jstr, "file_name")?;
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let mut diagnostics = parse_compiler_output(&output);
+            locate_diagnostics(&mut diagnostics, document.lock_or_recover().buffer(), &file_name, encoding);
+            to_json(&diagnostics, "compiler diagnostics")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Indexa un directorio de plantillas (`.cpp`, `.py`, `.js`, también en subcarpetas) y devuelve
+/// sus resúmenes (name, language, description, requires, headers) en JSON.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_indexTemplates(
+    mut env: JNIEnv,
+    _class: JClass,
+    directory_jstr: JString,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = get_rust_string(env, &directory_jstr, "directory")
+            .and_then(|directory| index_template_dir(std::path::Path::new(&directory)))
+            .and_then(|templates| to_json(&templates, "templates"));
+        return_string(env, result)
+    })
+}
+
+/// Registra una plantilla desde su contenido; `name` se usa si no trae `@name`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_registerTemplate(
+    mut env: JNIEnv,
+    _class: JClass,
+    language_jstr: JString,
+    name_jstr: JString,
+    content_jstr: JString,
+) {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let language = get_rust_string(env, &language_jstr, "language")?;
+            let name = get_rust_string(env, &name_jstr, "name")?;
+            let content = get_rust_string(env, &content_jstr, "content")?;
+            register_template(parse_template(&language, &name, &content));
+            Ok(())
+        })();
+        unwrap_or_throw(env, result)
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_listTemplates(
+    mut env: JNIEnv,
+    _class: JClass,
+    language_jstr: JString,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = get_rust_string(env, &language_jstr, "language")
+            .and_then(|language| to_json(&list_templates(&language), "templates"));
+        return_string(env, result)
+    })
+}
+
+/// Inserta una plantilla y sus dependencias en el documento: devuelve `edits` (start, end,
+/// text) según `offsetEncoding`, más las plantillas y cabeceras añadidas. No modifica el
+/// documento: los edits se aplican con `applyEdits`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_insertTemplate(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    name_jstr: JString,
+    offset: jint,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let name = get_rust_string(env, &name_jstr, "name")?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let cursor = document.buffer().to_byte(to_offset(offset, "offset")?, encoding);
+
+            let mut insertion = insert_template(&document, &name, cursor)?;
+            convert_edits(document.buffer(), &mut insertion.edits, encoding);
+            to_json(&insertion, "template insertion")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Formatea el documento y devuelve `edits` mínimos (start, end, text) según `offsetEncoding`
+/// y el `formatter` usado (clang-format, black, prettier o tree-sitter). `optionsJson` vacío
+/// usa las opciones por defecto. No modifica el documento.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_format(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options_json_jstr: JString,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let options: FormatOptions = parse_options(env, &options_json_jstr, "options_json")?;
+            let document = get_document(handle_to_id(handle)?)?;
+            // Copias baratas (rope y árbol compartidos) para no retener el documento mientras
+            // corre el formateador externo
+            let (language, buffer, tree) = {
+                let document = document.lock_or_recover();
+                (document.language_name.clone(), document.buffer().clone(), document.tree.clone())
+            };
+
+            let mut formatted = format_document(&language, &buffer.to_string(), &tree, &options);
+            convert_edits(&buffer, &mut formatted.edits, OffsetEncoding::from_jint(offset_encoding)?);
+            to_json(&formatted, "format result")
+        })();
+        return_string(env, result)
+    })
+}
 
-    env.new_string(output).unwrap().into_raw()
+/// Busca `queryJson` (pattern, regex, whole_word, case_sensitive y scope: all, code, comments
+/// o strings) desde `from`, hasta `limit` resultados (0 = todos). Devuelve `matches` (start,
+/// end) y `next`, el offset desde el que pedir la siguiente tanda, o null si no hay más.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_findInDocument(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    query_json_jstr: JString,
+    from: jint,
+    limit: jint,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let query: SearchQuery = parse_options(env, &query_json_jstr, "query_json")?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            let encoding = OffsetEncoding::from_jint(offset_encoding)?;
+            let buffer = document.buffer();
+            let from = buffer.to_byte(to_offset(from, "from")?, encoding);
+
+            let mut batch = find_matches(&document, &query, from, to_offset(limit, "limit")?)?;
+            for found in &mut batch.matches {
+                found.start = buffer.convert(found.start, encoding);
+                found.end = buffer.convert(found.end, encoding);
+            }
+            batch.next = batch.next.map(|next| buffer.convert(next, encoding));
+            to_json(&batch, "search results")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Edits (start, end, text) que reemplazan todas las coincidencias de `queryJson` por
+/// `replacement` (`$1` en modo regex). No modifica el documento: se aplican con `applyEdits`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_replaceAll(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    query_json_jstr: JString,
+    replacement_jstr: JString,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let query: SearchQuery = parse_options(env, &query_json_jstr, "query_json")?;
+            let replacement = get_rust_string(env, &replacement_jstr, "replacement")?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+
+            let mut edits = replace_all(&document, &query, &replacement)?;
+            convert_edits(document.buffer(), &mut edits, OffsetEncoding::from_jint(offset_encoding)?);
+            to_json(&edits, "replace edits")
+        })();
+        return_string(env, result)
+    })
+}
+
+/// Ejecuta una query de tree-sitter sobre el documento. Devuelve `{"status": "error", "error":
+/// {row, column, offset, kind, message}}` si no compila o `{"status": "ok", "captures": [...],
+/// "pattern_count", "truncated"}` con nombre, nodo y rango de cada captura según `offsetEncoding`.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_runQuery(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    query_source_jstr: JString,
+    offset_encoding: jint,
+) -> jstring {
+    jni_guard(&mut env, |env| {
+        let result = (|| {
+            let source = get_rust_string(env, &query_source_jstr, "query_source")?;
+            let document = get_document(handle_to_id(handle)?)?;
+            let document = document.lock_or_recover();
+            to_json(&run_query(&document, &source, OffsetEncoding::from_jint(offset_encoding)?), "query result")
+        })();
+        return_string(env, result)
+    })
 }
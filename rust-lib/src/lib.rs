@@ -1,12 +1,34 @@
+pub mod brackets;
+pub mod buffer;
 pub mod cache;
 pub mod code_exec;
+pub mod compiler_diagnostics;
+pub mod completion;
+pub mod document;
+pub mod encoding;
+pub mod error;
+pub mod export;
+pub mod formatter;
 pub mod future;
 pub mod highlighting;
+pub mod history;
 pub mod incremental;
 pub mod jni_bridge;
 
 pub mod logger;
+pub mod multi_cursor;
+pub mod offsets;
+pub mod query_playground;
+pub mod search;
+pub mod selection;
+pub mod semantic_tokens;
+pub mod snippets;
+pub mod symbols;
+pub mod syntax_diagnostics;
+pub mod templates;
 pub mod text_utils;
 pub mod theme;
+pub mod theme_import;
+pub mod theme_validation;
 pub mod types;
 pub mod utils;
@@ -2,15 +2,18 @@ use jni::JNIEnv;
 use jni::objects::JClass;
 use android_logger::Config;
 use log::LevelFilter;
+use crate::error::jni_guard;
 
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_example_lancelot_rust_RustBridge_initLogger(
-    _env: JNIEnv,
+    mut env: JNIEnv,
     _class: JClass,
 ) {
-    android_logger::init_once(
-        Config::default()
-            .with_max_level(LevelFilter::Debug)
-            .with_tag("RustBridge"),
-    );
+    jni_guard(&mut env, |_| {
+        android_logger::init_once(
+            Config::default()
+                .with_max_level(LevelFilter::Debug)
+                .with_tag("RustBridge"),
+        );
+    })
 }
@@ -0,0 +1,212 @@
+use std::ops::Range;
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::TextBuffer;
+use crate::history::{Change, EditKind};
+use crate::types::TextEdit;
+
+/// Selección o cursor (si `anchor == head`); `head` es el extremo que se mueve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    pub fn caret(offset: usize) -> Self {
+        Self { anchor: offset, head: offset }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+}
+
+/// Edición lógica que se aplica en cada selección.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SelectionEdit {
+    /// Escribe `text` en lugar de lo seleccionado y deja el cursor detrás
+    Insert { text: String },
+    /// Como `Insert`, pero deja seleccionado el texto nuevo (renombrar ocurrencias)
+    Replace { text: String },
+    /// Borra lo seleccionado o, en un cursor, el carácter anterior
+    DeleteBackward,
+    /// Borra lo seleccionado o, en un cursor, el carácter siguiente
+    DeleteForward,
+}
+
+/// Edits sobre el texto original (ordenados, sin solapes) y selecciones sobre el resultado.
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectionEditResult {
+    pub edits: Vec<TextEdit>,
+    pub selections: Vec<Selection>,
+}
+
+impl SelectionEditResult {
+    /// Tipo de transacción para el historial. Cada selección recibe la misma edición, así que
+    /// se clasifica la primera con la regla de `EditKind::classify`.
+    pub fn kind(&self, buffer: &TextBuffer) -> EditKind {
+        let changes: Vec<Change> = self.edits.first()
+            .map(|edit| Change { start: edit.start, removed: buffer.slice(edit.start..edit.end), inserted: edit.text.clone() })
+            .into_iter()
+            .collect();
+        EditKind::classify(&changes)
+    }
+}
+
+/// Ordena las selecciones y funde las que se solapan o coinciden. Un cursor pegado al borde
+/// de una selección se absorbe; dos selecciones que sólo se tocan se conservan.
+pub fn normalize_selections(selections: &[Selection], len: usize) -> Vec<Selection> {
+    let mut sorted: Vec<Selection> = selections.iter()
+        .map(|s| Selection { anchor: s.anchor.min(len), head: s.head.min(len) })
+        .collect();
+    sorted.sort_by_key(|s| (s.range().start, s.range().end));
+
+    let mut merged: Vec<Selection> = Vec::with_capacity(sorted.len());
+    for selection in sorted {
+        if let Some(last) = merged.last_mut() {
+            let (previous, current) = (last.range(), selection.range());
+            let touching = current.start == previous.end && (last.is_empty() || selection.is_empty());
+            if current.start < previous.end || touching {
+                let end = previous.end.max(current.end);
+                // Se conserva la orientación de la primera
+                *last = if last.anchor <= last.head {
+                    Selection { anchor: previous.start, head: end }
+                } else {
+                    Selection { anchor: end, head: previous.start }
+                };
+                continue;
+            }
+        }
+        merged.push(selection);
+    }
+    merged
+}
+
+// Un carácter ocupa como mucho 4 bytes; `chunks` ajusta el inicio al carácter entero
+fn previous_char_start(buffer: &TextBuffer, offset: usize) -> usize {
+    let before: String = buffer.chunks(offset.saturating_sub(4)..offset).collect();
+    before.chars().next_back().map_or(offset, |c| offset - c.len_utf8())
+}
+
+fn next_char_end(buffer: &TextBuffer, offset: usize) -> usize {
+    buffer.chunks(offset..buffer.len()).flat_map(str::chars).next().map_or(offset, |c| offset + c.len_utf8())
+}
+
+/// Calcula la edición de todas las selecciones sin tocar el documento, para aplicarla de una
+/// vez con `Document::apply_edits`. Los borrados de cursores contiguos que se solapan se
+/// funden en uno.
+pub fn plan_selection_edit(buffer: &TextBuffer, selections: &[Selection], edit: &SelectionEdit) -> SelectionEditResult {
+    let selections = normalize_selections(selections, buffer.len());
+    let (inserted, select_inserted) = match edit {
+        SelectionEdit::Insert { text } => (text.as_str(), false),
+        SelectionEdit::Replace { text } => (text.as_str(), true),
+        SelectionEdit::DeleteBackward | SelectionEdit::DeleteForward => ("", false),
+    };
+
+    let mut ranges: Vec<Range<usize>> = Vec::with_capacity(selections.len());
+    for selection in &selections {
+        let mut range = selection.range();
+        if range.is_empty() {
+            match edit {
+                SelectionEdit::DeleteBackward => range.start = previous_char_start(buffer, range.start),
+                SelectionEdit::DeleteForward => range.end = next_char_end(buffer, range.end),
+                _ => {}
+            }
+        }
+        match ranges.last_mut() {
+            Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+            // Dos cursores en el mismo sitio ya se fundieron al normalizar
+            _ => ranges.push(range),
+        }
+    }
+
+    let mut edits = Vec::with_capacity(ranges.len());
+    let mut new_selections = Vec::with_capacity(ranges.len());
+    let mut shift = 0isize;
+    for range in ranges {
+        if range.is_empty() && inserted.is_empty() {
+            // Borrar al principio o al final del texto: nada que hacer, el cursor se queda
+            new_selections.push(Selection::caret(range.start.saturating_add_signed(shift)));
+            continue;
+        }
+        let start = range.start.saturating_add_signed(shift);
+        let end = start + inserted.len();
+        new_selections.push(if select_inserted { Selection { anchor: start, head: end } } else { Selection::caret(end) });
+        shift += inserted.len() as isize - range.len() as isize;
+        edits.push(TextEdit::new(range.start, range.end, inserted));
+    }
+    let len = buffer.len().saturating_add_signed(shift);
+    SelectionEditResult { edits, selections: normalize_selections(&new_selections, len) }
+}
+
+/// Añade la siguiente aparición del texto de la última selección (Ctrl+D). Si la última es
+/// un cursor, primero se amplía a la palabra que lo contiene.
+pub fn add_next_occurrence(text: &str, selections: &[Selection]) -> Vec<Selection> {
+    let mut selections = normalize_selections(selections, text.len());
+    let Some(last) = selections.last().copied() else {
+        return selections;
+    };
+    if last.is_empty() {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let start = text[..last.head].char_indices().rev()
+            .take_while(|&(_, c)| is_word(c))
+            .last()
+            .map_or(last.head, |(i, _)| i);
+        let end = text[last.head..].char_indices()
+            .find(|&(_, c)| !is_word(c))
+            .map_or(text.len(), |(i, _)| last.head + i);
+        if start < end {
+            *selections.last_mut().unwrap() = Selection { anchor: start, head: end };
+        }
+        return selections;
+    }
+
+    let needle = &text[last.range()];
+    let after = last.range().end;
+    let found = text[after..].find(needle).map(|i| after + i)
+        .or_else(|| text[..after].find(needle))
+        .filter(|&start| !selections.iter().any(|s| s.range().start == start));
+    if let Some(start) = found {
+        selections.push(Selection { anchor: start, head: start + needle.len() });
+        selections = normalize_selections(&selections, text.len());
+    }
+    selections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::apply_edits;
+
+    #[test]
+    fn renames_occurrences_and_deletes_with_many_cursors() {
+        let code = "for (int i = 0; i < n; i++) s += n;";
+        let mut selections = add_next_occurrence(code, &[Selection::caret(code.find("n;").unwrap())]);
+        selections = add_next_occurrence(code, &selections);
+        assert_eq!(selections.len(), 2);
+
+        let rename = plan_selection_edit(&TextBuffer::new(code), &selections, &SelectionEdit::Replace { text: "len".into() });
+        let renamed = apply_edits(code, &rename.edits);
+        assert_eq!(renamed, "for (int i = 0; i < len; i++) s += len;");
+        assert!(rename.selections.iter().all(|s| &renamed[s.range()] == "len"));
+        assert_eq!(rename.kind(&TextBuffer::new(code)), EditKind::Other);
+
+        // Backspace en dos cursores contiguos y en el inicio del texto
+        let carets = [Selection::caret(0), Selection::caret(3), Selection::caret(4)];
+        let deleted = plan_selection_edit(&TextBuffer::new("abcdef"), &carets, &SelectionEdit::DeleteBackward);
+        assert_eq!(apply_edits("abcdef", &deleted.edits), "abef");
+        assert_eq!(deleted.selections, vec![Selection::caret(0), Selection::caret(2)]);
+        assert_eq!(deleted.kind(&TextBuffer::new("abcdef")), EditKind::Delete);
+
+        let typed = plan_selection_edit(&TextBuffer::new("a\nb\n"), &[Selection::caret(1), Selection::caret(3)], &SelectionEdit::Insert { text: ";".into() });
+        assert_eq!(apply_edits("a\nb\n", &typed.edits), "a;\nb;\n");
+        assert_eq!(typed.selections, vec![Selection::caret(2), Selection::caret(5)]);
+        assert_eq!(typed.kind(&TextBuffer::new("a\nb\n")), EditKind::Typing);
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{RunixError, RunixResult};
+
+/// Unidad en la que se expresan los offsets devueltos a Kotlin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffsetEncoding {
+    /// Bytes UTF-8 del `String` de Rust (lo que usa tree-sitter)
+    #[default]
+    Utf8,
+    /// Unidades de código UTF-16, como indexan los `String` de Kotlin/Compose
+    Utf16,
+}
+
+impl OffsetEncoding {
+    /// Traduce `RustBridge.OFFSET_UTF8` (0) y `OFFSET_UTF16` (1); cualquier otro valor es un error.
+    pub fn from_jint(value: i32) -> RunixResult<Self> {
+        match value {
+            0 => Ok(OffsetEncoding::Utf8),
+            1 => Ok(OffsetEncoding::Utf16),
+            other => Err(RunixError::InvalidArgument(format!("unknown offset encoding {}", other))),
+        }
+    }
+}
+
+/// Posición fila/columna; la columna está en la unidad de `OffsetEncoding` pedida.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextPoint {
+    pub row: usize,
+    pub column: usize,
+}
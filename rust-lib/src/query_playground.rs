@@ -0,0 +1,115 @@
+use serde::Serialize;
+use tree_sitter::{Node, Query, QueryCursor, QueryError, StreamingIterator};
+
+use crate::document::Document;
+use crate::error::kind_name;
+use crate::offsets::{OffsetEncoding, TextPoint};
+
+// Tope de capturas devueltas; una query como `(_) @all` sobre un archivo grande daría miles
+const MAX_CAPTURES: usize = 5000;
+// Longitud máxima del texto citado de cada captura
+const MAX_CAPTURE_TEXT_CHARS: usize = 80;
+
+/// Error de compilación de la query. `row`/`column` (0-based) y `offset` se refieren al
+/// fuente de la query, en bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryDiagnostic {
+    pub row: usize,
+    pub column: usize,
+    pub offset: usize,
+    /// syntax, node_type, field, capture, predicate, structure o language
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl From<QueryError> for QueryDiagnostic {
+    fn from(error: QueryError) -> Self {
+        Self { row: error.row, column: error.column, offset: error.offset, kind: kind_name(&error.kind), message: error.message }
+    }
+}
+
+/// Captura de la query sobre el documento, con offsets en la codificación pedida.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryCapture {
+    pub name: String,
+    pub pattern_index: usize,
+    pub node_kind: &'static str,
+    pub start: usize,
+    pub end: usize,
+    pub start_point: TextPoint,
+    pub end_point: TextPoint,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum QueryRun {
+    Error { error: QueryDiagnostic },
+    Ok { captures: Vec<QueryCapture>, pattern_count: usize, truncated: bool },
+}
+
+/// Compila `source` para el lenguaje del documento y devuelve el error exacto o todas las
+/// capturas en orden, con los predicados (`#eq?`, `#match?`...) ya aplicados. Las líneas
+/// `; inherits:` no se resuelven: se prueba la query tal cual la ve Kotlin.
+pub fn run_query(document: &Document, source: &str, encoding: OffsetEncoding) -> QueryRun {
+    let query = match Query::new(&document.language, source) {
+        Ok(query) => query,
+        Err(error) => return QueryRun::Error { error: error.into() },
+    };
+    let buffer = document.buffer();
+    let names = query.capture_names();
+
+    let mut captures = Vec::new();
+    let mut truncated = false;
+    let mut cursor = QueryCursor::new();
+    let mut iter = cursor.captures(&query, document.tree.root_node(), |node: Node| buffer.chunks(node.byte_range()));
+    while let Some((query_match, capture_index)) = iter.next() {
+        if captures.len() == MAX_CAPTURES {
+            truncated = true;
+            break;
+        }
+        let capture = query_match.captures[*capture_index];
+        let node = capture.node;
+        let mut quoted: String = buffer.chunks(node.byte_range()).flat_map(str::chars).take(MAX_CAPTURE_TEXT_CHARS).collect();
+        if quoted.len() < node.byte_range().len() {
+            quoted.push('…');
+        }
+        captures.push(QueryCapture {
+            name: names[capture.index as usize].to_string(),
+            pattern_index: query_match.pattern_index,
+            node_kind: node.kind(),
+            start: buffer.convert(node.start_byte(), encoding),
+            end: buffer.convert(node.end_byte(), encoding),
+            start_point: buffer.byte_to_point(node.start_byte(), encoding),
+            end_point: buffer.byte_to_point(node.end_byte(), encoding),
+            text: quoted,
+        });
+    }
+    QueryRun::Ok { captures, pattern_count: query.pattern_count(), truncated }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_query_errors_and_captures() {
+        let code = "int main() { return foo(1); }";
+        let document = Document::for_test("cpp", code, "");
+
+        let QueryRun::Error { error } = run_query(&document, "(call_expression\n  function: (identifer) @f)", OffsetEncoding::Utf8) else {
+            panic!("expected a query error");
+        };
+        assert_eq!((error.row, error.kind), (1, "node_type"));
+
+        let source = "(call_expression function: (identifier) @function.call)\n((identifier) @main (#eq? @main \"main\"))";
+        let QueryRun::Ok { captures, pattern_count, truncated } = run_query(&document, source, OffsetEncoding::Utf8) else {
+            panic!("expected captures");
+        };
+        assert_eq!(pattern_count, 2);
+        assert!(!truncated);
+        let found: Vec<(&str, &str)> = captures.iter().map(|c| (c.name.as_str(), c.text.as_str())).collect();
+        assert_eq!(found, vec![("main", "main"), ("function.call", "foo")]);
+        assert_eq!(captures[1].start_point, TextPoint { row: 0, column: code.find("foo").unwrap() });
+    }
+}
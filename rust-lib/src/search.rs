@@ -0,0 +1,157 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use tree_sitter::Tree;
+
+use crate::document::Document;
+use crate::error::{RunixError, RunixResult};
+use crate::types::TextEdit;
+
+/// Zona del código en la que se busca, según el árbol de sintaxis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchScope {
+    #[default]
+    All,
+    Code,
+    Comments,
+    Strings,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub regex: bool,
+    pub whole_word: bool,
+    pub case_sensitive: bool,
+    pub scope: SearchScope,
+}
+
+impl SearchQuery {
+    fn compile(&self) -> RunixResult<Regex> {
+        if self.pattern.is_empty() {
+            return Err(RunixError::InvalidArgument("empty search pattern".into()));
+        }
+        let pattern = if self.regex { self.pattern.clone() } else { regex::escape(&self.pattern) };
+        let pattern = if self.whole_word { format!(r"\b(?:{})\b", pattern) } else { pattern };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+            .map_err(|e| RunixError::InvalidArgument(format!("invalid search pattern: {}", e)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SearchMatch {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Tanda de resultados; si `next` no es `None`, la siguiente se pide desde ese offset.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchBatch {
+    pub matches: Vec<SearchMatch>,
+    pub next: Option<usize>,
+}
+
+// Zona del nodo más pequeño que contiene el inicio de la coincidencia
+fn scope_at(tree: &Tree, offset: usize) -> SearchScope {
+    let mut node = tree.root_node().descendant_for_byte_range(offset, offset);
+    while let Some(current) = node {
+        let kind = current.kind();
+        if kind.contains("comment") {
+            return SearchScope::Comments;
+        }
+        if kind.contains("string") || kind == "char_literal" {
+            return SearchScope::Strings;
+        }
+        node = current.parent();
+    }
+    SearchScope::Code
+}
+
+fn in_scope(tree: &Tree, scope: SearchScope, offset: usize) -> bool {
+    scope == SearchScope::All || scope_at(tree, offset) == scope
+}
+
+/// Hasta `limit` coincidencias a partir de `from` (0 = sin límite).
+pub fn find_matches(document: &Document, query: &SearchQuery, from: usize, limit: usize) -> RunixResult<SearchBatch> {
+    let regex = query.compile()?;
+    // `regex` necesita el texto contiguo: `text()` lo materializa una vez por versión del
+    // documento y las tandas siguientes lo reutilizan
+    let text = document.text();
+    let from = from.min(text.len());
+    let mut matches = Vec::new();
+    let mut at = from;
+    // `find_at` en lugar de recortar el texto para que `\b` y `^` vean lo anterior a `from`
+    while at <= text.len() {
+        let Some(found) = regex.find_at(text, at) else {
+            break;
+        };
+        at = if found.is_empty() {
+            text[found.end()..].chars().next().map_or(text.len() + 1, |c| found.end() + c.len_utf8())
+        } else {
+            found.end()
+        };
+        if !in_scope(&document.tree, query.scope, found.start()) {
+            continue;
+        }
+        if limit > 0 && matches.len() == limit {
+            return Ok(SearchBatch { matches, next: Some(found.start()) });
+        }
+        matches.push(SearchMatch { start: found.start(), end: found.end() });
+    }
+    Ok(SearchBatch { matches, next: None })
+}
+
+/// Edits que reemplazan todas las coincidencias. Con `regex`, `replacement` admite `$1` o
+/// `${name}`; si no, se inserta tal cual.
+pub fn replace_all(document: &Document, query: &SearchQuery, replacement: &str) -> RunixResult<Vec<TextEdit>> {
+    let regex = query.compile()?;
+    let text = document.text();
+    let mut edits = Vec::new();
+    for captures in regex.captures_iter(text) {
+        let found = captures.get(0).expect("group 0 always matches");
+        if !in_scope(&document.tree, query.scope, found.start()) {
+            continue;
+        }
+        let mut replaced = String::new();
+        if query.regex {
+            captures.expand(replacement, &mut replaced);
+        } else {
+            replaced.push_str(replacement);
+        }
+        edits.push(TextEdit::new(found.start(), found.end(), replaced));
+    }
+    Ok(edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_in_batches_and_filters_by_scope() {
+        let code = "int n = 1; // n es el tamaño\nconst char* s = \"n\";\nint N = n + nn;\n";
+        let document = Document::for_test("cpp", code, "");
+        let words = SearchQuery { pattern: "n".into(), whole_word: true, case_sensitive: true, ..SearchQuery::default() };
+
+        let first = find_matches(&document, &words, 0, 2).unwrap();
+        assert_eq!(first.matches.len(), 2);
+        let rest = find_matches(&document, &words, first.next.unwrap(), 0).unwrap();
+        assert_eq!(rest.matches.len(), 2);
+        assert_eq!(rest.next, None);
+
+        let code_only = SearchQuery { scope: SearchScope::Code, ..words.clone() };
+        let starts: Vec<usize> = find_matches(&document, &code_only, 0, 0).unwrap().matches.iter().map(|m| m.start).collect();
+        assert_eq!(starts, vec![code.find("n =").unwrap(), code.find("n +").unwrap()]);
+        let comments = SearchQuery { scope: SearchScope::Comments, ..words.clone() };
+        assert_eq!(find_matches(&document, &comments, 0, 0).unwrap().matches.len(), 1);
+
+        let regex = SearchQuery { pattern: r"int (\w)".into(), regex: true, ..SearchQuery::default() };
+        let edits = replace_all(&document, &regex, "long long $1").unwrap();
+        assert_eq!(edits.iter().map(|e| e.text.as_str()).collect::<Vec<_>>(), vec!["long long n", "long long N"]);
+        assert!(find_matches(&document, &SearchQuery { pattern: "(".into(), regex: true, ..SearchQuery::default() }, 0, 0).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::ops::Range;
+use tree_sitter::{Node, Tree};
+
+/// Rango del nodo con nombre más pequeño que contiene `selection` y es estrictamente mayor
+/// que ella. `None` si la selección ya cubre la raíz.
+pub fn expand_selection(tree: &Tree, selection: Range<usize>) -> Option<Range<usize>> {
+    let mut node = tree.root_node().named_descendant_for_byte_range(selection.start, selection.end)?;
+    loop {
+        let range = node.byte_range();
+        if covers(&range, &selection) && range != selection {
+            return Some(range);
+        }
+        node = node.parent()?;
+    }
+}
+
+/// Rango del primer nodo con nombre estrictamente contenido en `selection`: el hijo que
+/// contiene su inicio o, si no lo hay, el primero. `None` si no queda nada más pequeño.
+pub fn shrink_selection(tree: &Tree, selection: Range<usize>) -> Option<Range<usize>> {
+    let mut node = tree.root_node().named_descendant_for_byte_range(selection.start, selection.end)?;
+    // Subimos hasta el nodo más externo que coincide con la selección
+    while let Some(parent) = node.parent().filter(|p| p.byte_range() == selection) {
+        node = parent;
+    }
+
+    loop {
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor)
+            .filter(|child| covers(&selection, &child.byte_range()) && !child.byte_range().is_empty())
+            .collect();
+        let child = children.iter()
+            .find(|child| child.byte_range().contains(&selection.start))
+            .or(children.first())
+            .copied()?;
+        if child.byte_range() != selection {
+            return Some(child.byte_range());
+        }
+        node = child;
+    }
+}
+
+fn covers(outer: &Range<usize>, inner: &Range<usize>) -> bool {
+    outer.start <= inner.start && outer.end >= inner.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::highlighting::load_language;
+    use tree_sitter::Parser;
+
+    #[test]
+    fn expands_and_shrinks_through_named_nodes() {
+        let code = "int main() { if (a > b) { f(a, b); } }";
+        let mut parser = Parser::new();
+        parser.set_language(&load_language("cpp").unwrap()).unwrap();
+        let tree = parser.parse(code, None).unwrap();
+        let text = |range: Range<usize>| &code[range];
+
+        // Cursor sobre el segundo argumento de f
+        let cursor = code.find("b);").unwrap();
+        let argument = expand_selection(&tree, cursor..cursor).unwrap();
+        assert_eq!(text(argument.clone()), "b");
+        let arguments = expand_selection(&tree, argument.clone()).unwrap();
+        assert_eq!(text(arguments.clone()), "(a, b)");
+        let call = expand_selection(&tree, arguments.clone()).unwrap();
+        assert_eq!(text(call.clone()), "f(a, b)");
+
+        let if_start = code.find("if").unwrap();
+        let if_block = expand_selection(&tree, if_start..if_start + 2).unwrap();
+        assert_eq!(text(if_block.clone()), "if (a > b) { f(a, b); }");
+
+        assert_eq!(text(shrink_selection(&tree, call).unwrap()), "f");
+        assert_eq!(text(shrink_selection(&tree, if_block).unwrap()), "(a > b)");
+        assert_eq!(expand_selection(&tree, 0..code.len()), None);
+    }
+}
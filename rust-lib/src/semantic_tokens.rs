@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use log::warn;
+use serde::Serialize;
+
+use crate::buffer::TextBuffer;
+use crate::offsets::OffsetEncoding;
+use crate::types::HighlightRange;
+
+/// Leyenda LSP derivada de los nombres de resaltado: la primera parte del nombre es el tipo
+/// de token y el resto son modificadores (`function.builtin` → tipo `function`, modificador `builtin`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensLegend {
+    pub token_types: Vec<String>,
+    pub token_modifiers: Vec<String>,
+    // (tipo, máscara de modificadores) para cada índice de resaltado
+    #[serde(skip)]
+    highlight_tokens: Vec<(u32, u32)>,
+}
+
+impl SemanticTokensLegend {
+    pub fn new(highlight_names: &[String]) -> Self {
+        let mut legend = SemanticTokensLegend::default();
+        let mut type_ids: HashMap<&str, u32> = HashMap::new();
+        let mut modifier_ids: HashMap<&str, Option<u32>> = HashMap::new();
+
+        for name in highlight_names {
+            let mut parts = name.split('.');
+            let token_type = parts.next().unwrap_or_default();
+            let type_id = *type_ids.entry(token_type).or_insert_with(|| {
+                legend.token_types.push(token_type.to_string());
+                legend.token_types.len() as u32 - 1
+            });
+            let mut modifiers = 0u32;
+            for modifier in parts {
+                // LSP sólo admite 32 modificadores en la máscara; los demás no entran en la leyenda
+                let bit = *modifier_ids.entry(modifier).or_insert_with(|| {
+                    if legend.token_modifiers.len() >= 32 {
+                        warn!("Semantic token modifier '{}' of '{}' exceeds the 32-bit mask and is dropped", modifier, name);
+                        return None;
+                    }
+                    legend.token_modifiers.push(modifier.to_string());
+                    Some(legend.token_modifiers.len() as u32 - 1)
+                });
+                if let Some(bit) = bit {
+                    modifiers |= 1 << bit;
+                }
+            }
+            legend.highlight_tokens.push((type_id, modifiers));
+        }
+        legend
+    }
+
+    pub fn token_for(&self, highlight_type: usize) -> Option<(u32, u32)> {
+        self.highlight_tokens.get(highlight_type).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokens {
+    pub result_id: String,
+    pub data: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub data: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDelta {
+    pub result_id: String,
+    pub edits: Vec<SemanticTokensEdit>,
+}
+
+/// Respuesta de `semanticTokensDelta`: edits si la versión anterior sigue disponible,
+/// o los tokens completos si no.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum SemanticTokensResult {
+    Full(SemanticTokens),
+    Delta(SemanticTokensDelta),
+}
+
+/// Codifica los rangos en el formato relativo de LSP (`deltaLine, deltaStart, length,
+/// tokenType, tokenModifiers`), con columnas y longitudes en UTF-16. Los tokens que
+/// cruzan líneas se parten en un token por línea, sin incluir el `\r` de un fin de línea CRLF.
+pub fn encode_semantic_tokens(ranges: &[HighlightRange], buffer: &TextBuffer, legend: &SemanticTokensLegend) -> Vec<u32> {
+    let mut sorted: Vec<&HighlightRange> = ranges.iter().filter(|r| r.start < r.end).collect();
+    sorted.sort_by_key(|r| r.start);
+
+    let mut data = Vec::with_capacity(sorted.len() * 5);
+    let (mut prev_line, mut prev_start) = (0usize, 0usize);
+    for range in sorted {
+        let Some((token_type, modifiers)) = legend.token_for(range.highlight_type) else {
+            continue;
+        };
+        let end = range.end.min(buffer.len());
+        let mut start = range.start;
+        while start < end {
+            let row = buffer.point_at(start).row;
+            let token_end = buffer.line_end(row).min(end);
+            if token_end > start {
+                let point = buffer.byte_to_point(start, OffsetEncoding::Utf16);
+                let length = buffer.convert(token_end, OffsetEncoding::Utf16) - buffer.convert(start, OffsetEncoding::Utf16);
+                let delta_line = point.row - prev_line;
+                let delta_start = if delta_line == 0 { point.column - prev_start } else { point.column };
+                data.extend_from_slice(&[delta_line as u32, delta_start as u32, length as u32, token_type, modifiers]);
+                prev_line = point.row;
+                prev_start = point.column;
+            }
+            start = if row + 1 < buffer.line_count() { buffer.line_start(row + 1) } else { end };
+        }
+    }
+    data
+}
+
+/// Edits mínimos (prefijo y sufijo comunes, en unidades de token) para pasar de `old` a `new`.
+pub fn diff_semantic_tokens(old: &[u32], new: &[u32]) -> Vec<SemanticTokensEdit> {
+    if old == new {
+        return Vec::new();
+    }
+    let tokens = |data: &[u32]| data.len() / 5;
+    let same = |a: &[u32], i: usize, b: &[u32], j: usize| a[i * 5..i * 5 + 5] == b[j * 5..j * 5 + 5];
+
+    let mut prefix = 0;
+    while prefix < tokens(old) && prefix < tokens(new) && same(old, prefix, new, prefix) {
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < tokens(old) - prefix && suffix < tokens(new) - prefix
+        && same(old, tokens(old) - 1 - suffix, new, tokens(new) - 1 - suffix) {
+        suffix += 1;
+    }
+
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: ((tokens(old) - prefix - suffix) * 5) as u32,
+        data: new[prefix * 5..(tokens(new) - suffix) * 5].to_vec(),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_relative_utf16_positions() {
+        let names = vec!["keyword".to_string(), "function.builtin".to_string(), "comment".to_string()];
+        let legend = SemanticTokensLegend::new(&names);
+        assert_eq!(legend.token_types, vec!["keyword", "function", "comment"]);
+        assert_eq!(legend.token_modifiers, vec!["builtin"]);
+
+        // "ñ" ocupa dos bytes pero una sola unidad UTF-16
+        let text = "/*ñ\n*/ int printf";
+        let ranges = vec![
+            HighlightRange::new(0, 7, 2),
+            HighlightRange::new(8, 11, 0),
+            HighlightRange::new(12, 18, 1),
+        ];
+        let data = encode_semantic_tokens(&ranges, &TextBuffer::new(text), &legend);
+        assert_eq!(data, vec![
+            0, 0, 3, 2, 0,
+            1, 0, 2, 2, 0,
+            0, 3, 3, 0, 0,
+            0, 4, 6, 1, 1,
+        ]);
+    }
+
+    #[test]
+    fn excludes_carriage_return_and_extra_modifiers() {
+        let legend = SemanticTokensLegend::new(&["comment".to_string()]);
+        let data = encode_semantic_tokens(&[HighlightRange::new(0, 10, 0)], &TextBuffer::new("/* a\r\nb */"), &legend);
+        assert_eq!(data, vec![0, 0, 4, 0, 0, 1, 0, 4, 0, 0]);
+
+        let mut names: Vec<String> = (0..33).map(|i| format!("variable.m{}", i)).collect();
+        names.push("variable.m0.m32".to_string());
+        let legend = SemanticTokensLegend::new(&names);
+        assert_eq!(legend.token_modifiers.len(), 32);
+        assert_eq!(legend.token_for(32), Some((0, 0)));
+        assert_eq!(legend.token_for(33), Some((0, 1)));
+    }
+
+    #[test]
+    fn diff_replaces_only_changed_tokens() {
+        let old = vec![0, 0, 3, 0, 0, 1, 0, 2, 1, 0, 0, 4, 1, 2, 0];
+        let new = vec![0, 0, 3, 0, 0, 1, 0, 5, 1, 0, 0, 4, 1, 2, 0];
+        let edits = diff_semantic_tokens(&old, &new);
+        assert_eq!(edits, vec![SemanticTokensEdit { start: 5, delete_count: 5, data: vec![1, 0, 5, 1, 0] }]);
+    }
+}
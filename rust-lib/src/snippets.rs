@@ -0,0 +1,726 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use once_cell::sync::Lazy;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::LockExt;
+use crate::completion::fuzzy_score;
+use crate::error::{RunixError, RunixResult};
+
+/// Nodo de un snippet con la gramática de VS Code/LSP.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnippetNode {
+    Text(String),
+    /// `$1`, `${1}` o `${1/regex/format/opciones}`
+    TabStop { index: u32, transform: Option<Transform> },
+    /// `${1:valor por defecto}`; el contenido puede anidar otros nodos
+    Placeholder { index: u32, children: Vec<SnippetNode> },
+    /// `${1|uno,dos|}`
+    Choice { index: u32, options: Vec<String> },
+    /// `$TM_FILENAME`, `${TM_FILENAME:defecto}` o `${TM_FILENAME/regex/format/}`
+    Variable { name: String, default: Option<Vec<SnippetNode>>, transform: Option<Transform> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    pub regex: String,
+    pub format: Vec<FormatItem>,
+    pub options: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatItem {
+    Text(String),
+    /// `$1`, `${1:/upcase}`, `${1:+si}`, `${1:?si:no}`, `${1:-no}`...
+    Group { index: usize, case: Option<String>, if_text: Option<String>, else_text: Option<String> },
+}
+
+/// Parsea el cuerpo de un snippet. Lo que no encaja en la gramática (un `$` suelto, un
+/// `${` sin cerrar) se conserva como texto, igual que hace VS Code.
+pub fn parse_snippet(body: &str) -> Vec<SnippetNode> {
+    let mut parser = SnippetParser { chars: body.chars().collect(), pos: 0 };
+    parser.parse_nodes(false)
+}
+
+struct SnippetParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl SnippetParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_nodes(&mut self, nested: bool) -> Vec<SnippetNode> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '}' if nested => break,
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(escaped @ ('$' | '}' | '\\')) => {
+                            text.push(escaped);
+                            self.pos += 1;
+                        }
+                        _ => text.push('\\'),
+                    }
+                }
+                '$' => {
+                    let start = self.pos;
+                    match self.parse_dollar() {
+                        Some(node) => {
+                            if !text.is_empty() {
+                                nodes.push(SnippetNode::Text(std::mem::take(&mut text)));
+                            }
+                            nodes.push(node);
+                        }
+                        None => {
+                            self.pos = start + 1;
+                            text.push('$');
+                        }
+                    }
+                }
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if !text.is_empty() {
+            nodes.push(SnippetNode::Text(text));
+        }
+        nodes
+    }
+
+    fn parse_int(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+
+    fn parse_var_name(&mut self) -> Option<String> {
+        let start = self.pos;
+        if !self.peek().is_some_and(|c| c == '_' || c.is_ascii_alphabetic()) {
+            return None;
+        }
+        while self.peek().is_some_and(|c| c == '_' || c.is_ascii_alphanumeric()) {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn parse_dollar(&mut self) -> Option<SnippetNode> {
+        self.pos += 1; // '$'
+        if !self.eat('{') {
+            if let Some(index) = self.parse_int() {
+                return Some(SnippetNode::TabStop { index, transform: None });
+            }
+            let name = self.parse_var_name()?;
+            return Some(SnippetNode::Variable { name, default: None, transform: None });
+        }
+
+        if let Some(index) = self.parse_int() {
+            if self.eat('}') {
+                return Some(SnippetNode::TabStop { index, transform: None });
+            }
+            if self.eat(':') {
+                let children = self.parse_nodes(true);
+                return self.eat('}').then_some(SnippetNode::Placeholder { index, children });
+            }
+            if self.eat('|') {
+                let options = self.parse_choice_options()?;
+                return Some(SnippetNode::Choice { index, options });
+            }
+            if self.eat('/') {
+                let transform = self.parse_transform()?;
+                return Some(SnippetNode::TabStop { index, transform: Some(transform) });
+            }
+            return None;
+        }
+
+        let name = self.parse_var_name()?;
+        if self.eat('}') {
+            return Some(SnippetNode::Variable { name, default: None, transform: None });
+        }
+        if self.eat(':') {
+            let children = self.parse_nodes(true);
+            return self.eat('}').then_some(SnippetNode::Variable { name, default: Some(children), transform: None });
+        }
+        if self.eat('/') {
+            let transform = self.parse_transform()?;
+            return Some(SnippetNode::Variable { name, default: None, transform: Some(transform) });
+        }
+        None
+    }
+
+    // Opciones de `${1|a,b|}`: `\,` y `\|` escapan los separadores
+    fn parse_choice_options(&mut self) -> Option<Vec<String>> {
+        let mut options = Vec::new();
+        let mut current = String::new();
+        loop {
+            let c = self.peek()?;
+            self.pos += 1;
+            match c {
+                '\\' => match self.peek() {
+                    Some(escaped @ (',' | '|' | '\\' | '$' | '}')) => {
+                        current.push(escaped);
+                        self.pos += 1;
+                    }
+                    _ => current.push('\\'),
+                },
+                ',' => options.push(std::mem::take(&mut current)),
+                '|' => {
+                    options.push(current);
+                    return self.eat('}').then_some(options);
+                }
+                _ => current.push(c),
+            }
+        }
+    }
+
+    // Texto hasta el siguiente `terminator` sin escapar
+    fn parse_until(&mut self, terminator: char) -> Option<String> {
+        let mut text = String::new();
+        loop {
+            let c = self.peek()?;
+            self.pos += 1;
+            if c == terminator {
+                return Some(text);
+            }
+            if c == '\\' && self.peek() == Some(terminator) {
+                text.push(terminator);
+                self.pos += 1;
+            } else {
+                text.push(c);
+            }
+        }
+    }
+
+    // `regex/format/opciones}` (la barra inicial ya se ha consumido)
+    fn parse_transform(&mut self) -> Option<Transform> {
+        let regex = self.parse_until('/')?;
+        let format = self.parse_format()?;
+        let options = self.parse_until('}')?;
+        Some(Transform { regex, format, options })
+    }
+
+    fn parse_format(&mut self) -> Option<Vec<FormatItem>> {
+        let mut items = Vec::new();
+        let mut text = String::new();
+        loop {
+            let c = self.peek()?;
+            match c {
+                '/' => {
+                    self.pos += 1;
+                    break;
+                }
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(escaped @ ('/' | '$' | '\\')) => {
+                            text.push(escaped);
+                            self.pos += 1;
+                        }
+                        _ => text.push('\\'),
+                    }
+                }
+                '$' => {
+                    let start = self.pos;
+                    match self.parse_format_group() {
+                        Some(group) => {
+                            if !text.is_empty() {
+                                items.push(FormatItem::Text(std::mem::take(&mut text)));
+                            }
+                            items.push(group);
+                        }
+                        None => {
+                            self.pos = start + 1;
+                            text.push('$');
+                        }
+                    }
+                }
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if !text.is_empty() {
+            items.push(FormatItem::Text(text));
+        }
+        Some(items)
+    }
+
+    fn parse_format_group(&mut self) -> Option<FormatItem> {
+        self.pos += 1; // '$'
+        let group = |index: u32| FormatItem::Group { index: index as usize, case: None, if_text: None, else_text: None };
+        if !self.eat('{') {
+            return self.parse_int().map(group);
+        }
+        let index = self.parse_int()? as usize;
+        if self.eat('}') {
+            return Some(FormatItem::Group { index, case: None, if_text: None, else_text: None });
+        }
+        if !self.eat(':') {
+            return None;
+        }
+        if self.eat('/') {
+            let case = self.parse_var_name()?;
+            return self.eat('}').then_some(FormatItem::Group { index, case: Some(case), if_text: None, else_text: None });
+        }
+        if self.eat('+') {
+            let if_text = self.parse_until('}')?;
+            return Some(FormatItem::Group { index, case: None, if_text: Some(if_text), else_text: None });
+        }
+        if self.eat('?') {
+            let if_text = self.parse_until(':')?;
+            let else_text = self.parse_until('}')?;
+            return Some(FormatItem::Group { index, case: None, if_text: Some(if_text), else_text: Some(else_text) });
+        }
+        self.eat('-');
+        let else_text = self.parse_until('}')?;
+        Some(FormatItem::Group { index, case: None, if_text: None, else_text: Some(else_text) })
+    }
+}
+
+impl Transform {
+    /// Aplica la transformación a `value`; una regex inválida deja el valor intacto.
+    pub fn apply(&self, value: &str) -> String {
+        let regex = match RegexBuilder::new(&self.regex)
+            .case_insensitive(self.options.contains('i'))
+            .multi_line(self.options.contains('m'))
+            .build()
+        {
+            Ok(regex) => regex,
+            Err(_) => return value.to_string(),
+        };
+        let global = self.options.contains('g');
+        let mut out = String::new();
+        let mut last = 0;
+        for captures in regex.captures_iter(value) {
+            let whole = captures.get(0).expect("group 0 always exists");
+            out.push_str(&value[last..whole.start()]);
+            for item in &self.format {
+                match item {
+                    FormatItem::Text(text) => out.push_str(text),
+                    FormatItem::Group { index, case, if_text, else_text } => {
+                        let group = captures.get(*index).map(|m| m.as_str()).filter(|s| !s.is_empty());
+                        match (group, case) {
+                            (Some(group), Some(case)) => out.push_str(&apply_case(group, case)),
+                            (Some(group), None) => out.push_str(if_text.as_deref().unwrap_or(group)),
+                            (None, _) => out.push_str(else_text.as_deref().unwrap_or("")),
+                        }
+                    }
+                }
+            }
+            last = whole.end();
+            if !global {
+                break;
+            }
+        }
+        out.push_str(&value[last..]);
+        out
+    }
+}
+
+fn apply_case(value: &str, case: &str) -> String {
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+    };
+    match case {
+        "upcase" => value.to_uppercase(),
+        "downcase" => value.to_lowercase(),
+        "capitalize" => capitalize(value),
+        "camelcase" | "pascalcase" => {
+            let words: String = value.split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .map(capitalize)
+                .collect();
+            if case == "pascalcase" {
+                words
+            } else {
+                let mut chars = words.chars();
+                chars.next().map_or(String::new(), |first| first.to_lowercase().chain(chars).collect())
+            }
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Datos del editor para resolver variables. Las de fecha (`CURRENT_YEAR`...) y `RANDOM` se
+/// calculan aquí si no vienen en `variables`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SnippetContext {
+    pub variables: HashMap<String, String>,
+    /// Sangría de la línea donde se inserta; se añade tras cada salto de línea
+    pub indent: String,
+}
+
+impl SnippetContext {
+    fn variable(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.variables.get(name) {
+            return Some(value.clone());
+        }
+        if name == "TM_FILENAME_BASE" {
+            let file = self.variables.get("TM_FILENAME")?;
+            return Some(file.rsplit_once('.').map_or(file.as_str(), |(base, _)| base).to_string());
+        }
+        builtin_variable(name)
+    }
+}
+
+// Variables de fecha en UTC y números aleatorios sin depender de librerías externas
+fn builtin_variable(name: &str) -> Option<String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    let seconds = now.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time = seconds % 86_400;
+    Some(match name {
+        "CURRENT_YEAR" => year.to_string(),
+        "CURRENT_YEAR_SHORT" => format!("{:02}", year % 100),
+        "CURRENT_MONTH" => format!("{:02}", month),
+        "CURRENT_DATE" => format!("{:02}", day),
+        "CURRENT_HOUR" => format!("{:02}", time / 3600),
+        "CURRENT_MINUTE" => format!("{:02}", time / 60 % 60),
+        "CURRENT_SECOND" => format!("{:02}", time % 60),
+        "CURRENT_SECONDS_UNIX" => seconds.to_string(),
+        "RANDOM" => format!("{:06}", now.subsec_nanos() % 1_000_000),
+        "RANDOM_HEX" => format!("{:06x}", now.subsec_nanos() & 0xFF_FFFF),
+        _ => return None,
+    })
+}
+
+// Días desde 1970-01-01 a (año, mes, día) del calendario gregoriano
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Tab stop del snippet expandido: todas las apariciones del mismo índice se editan juntas.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnippetTabStop {
+    pub index: u32,
+    pub ranges: Vec<(usize, usize)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<String>>,
+}
+
+/// Texto expandido y tab stops en el orden en que se recorren (`$0` al final).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExpandedSnippet {
+    pub text: String,
+    pub tab_stops: Vec<SnippetTabStop>,
+}
+
+/// Expande `nodes`. Los rangos son offsets en bytes dentro de `text`. Las variables
+/// desconocidas sin valor por defecto se convierten en placeholders con su nombre, y si no
+/// hay `$0` se añade uno al final.
+pub fn expand_snippet(nodes: &[SnippetNode], context: &SnippetContext) -> ExpandedSnippet {
+    let mut expander = Expander {
+        context,
+        text: String::new(),
+        defaults: HashMap::new(),
+        ranges: BTreeMap::new(),
+        choices: HashMap::new(),
+        next_variable_index: max_index(nodes) + 1,
+        variable_indices: HashMap::new(),
+    };
+    expander.collect_defaults(nodes);
+    expander.emit(nodes, true);
+
+    let end = expander.text.len();
+    let mut ranges = expander.ranges;
+    let final_ranges = ranges.remove(&0).unwrap_or_else(|| vec![(end, end)]);
+    let mut tab_stops: Vec<SnippetTabStop> = ranges.into_iter()
+        .map(|(index, ranges)| SnippetTabStop { index, ranges, choices: expander.choices.remove(&index) })
+        .collect();
+    tab_stops.push(SnippetTabStop { index: 0, ranges: final_ranges, choices: expander.choices.remove(&0) });
+
+    ExpandedSnippet { text: expander.text, tab_stops }
+}
+
+fn max_index(nodes: &[SnippetNode]) -> u32 {
+    nodes.iter().map(|node| match node {
+        SnippetNode::TabStop { index, .. } | SnippetNode::Choice { index, .. } => *index,
+        SnippetNode::Placeholder { index, children } => (*index).max(max_index(children)),
+        SnippetNode::Variable { default: Some(children), .. } => max_index(children),
+        _ => 0,
+    }).max().unwrap_or(0)
+}
+
+struct Expander<'a> {
+    context: &'a SnippetContext,
+    text: String,
+    // Texto inicial de cada índice, para los espejos
+    defaults: HashMap<u32, String>,
+    ranges: BTreeMap<u32, Vec<(usize, usize)>>,
+    choices: HashMap<u32, Vec<String>>,
+    next_variable_index: u32,
+    // Índice asignado a cada variable desconocida
+    variable_indices: HashMap<String, u32>,
+}
+
+impl Expander<'_> {
+    fn collect_defaults(&mut self, nodes: &[SnippetNode]) {
+        for node in nodes {
+            match node {
+                SnippetNode::Placeholder { index, children } => {
+                    self.collect_defaults(children);
+                    if !self.defaults.contains_key(index) {
+                        let value = self.render_plain(children);
+                        self.defaults.insert(*index, value);
+                    }
+                }
+                SnippetNode::Choice { index, options } => {
+                    self.defaults.entry(*index).or_insert_with(|| options.first().cloned().unwrap_or_default());
+                }
+                SnippetNode::Variable { default: Some(children), .. } => self.collect_defaults(children),
+                _ => {}
+            }
+        }
+    }
+
+    // Expansión sin registrar rangos, para calcular el valor por defecto de un placeholder
+    fn render_plain(&mut self, nodes: &[SnippetNode]) -> String {
+        let saved_text = std::mem::take(&mut self.text);
+        let saved_ranges = std::mem::take(&mut self.ranges);
+        self.emit(nodes, false);
+        self.ranges = saved_ranges;
+        std::mem::replace(&mut self.text, saved_text)
+    }
+
+    fn push_text(&mut self, value: &str) {
+        if self.context.indent.is_empty() {
+            self.text.push_str(value);
+        } else {
+            self.text.push_str(&value.replace('\n', &format!("\n{}", self.context.indent)));
+        }
+    }
+
+    fn record(&mut self, index: u32, start: usize, record: bool) {
+        if record {
+            let end = self.text.len();
+            self.ranges.entry(index).or_default().push((start, end));
+        }
+    }
+
+    fn emit(&mut self, nodes: &[SnippetNode], record: bool) {
+        for node in nodes {
+            let start = self.text.len();
+            match node {
+                SnippetNode::Text(text) => self.push_text(text),
+                SnippetNode::TabStop { index, transform } => {
+                    let value = self.defaults.get(index).cloned().unwrap_or_default();
+                    match transform {
+                        Some(transform) => self.push_text(&transform.apply(&value)),
+                        None => {
+                            self.push_text(&value);
+                            self.record(*index, start, record);
+                        }
+                    }
+                }
+                SnippetNode::Placeholder { index, children } => {
+                    // Sólo el primer placeholder de un índice muestra su contenido con estructura;
+                    // el resto son espejos de ese valor
+                    if record && self.ranges.contains_key(index) {
+                        let value = self.defaults.get(index).cloned().unwrap_or_default();
+                        self.push_text(&value);
+                    } else {
+                        self.emit(children, record);
+                    }
+                    self.record(*index, start, record);
+                }
+                SnippetNode::Choice { index, options } => {
+                    let value = self.defaults.get(index).cloned().unwrap_or_default();
+                    self.push_text(&value);
+                    self.choices.entry(*index).or_insert_with(|| options.clone());
+                    self.record(*index, start, record);
+                }
+                SnippetNode::Variable { name, default, transform } => match self.context.variable(name) {
+                    Some(value) => {
+                        let value = match transform {
+                            Some(transform) => transform.apply(&value),
+                            None => value,
+                        };
+                        self.push_text(&value);
+                    }
+                    None => match default {
+                        Some(children) => self.emit(children, record),
+                        None => {
+                            let index = *self.variable_indices.entry(name.clone()).or_insert_with(|| {
+                                self.next_variable_index += 1;
+                                self.next_variable_index - 1
+                            });
+                            self.push_text(name);
+                            self.record(index, start, record);
+                        }
+                    },
+                },
+            }
+        }
+    }
+}
+
+/// Snippet de un paquete JSON en el formato de VS Code.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnippetDefinition {
+    pub name: String,
+    pub prefixes: Vec<String>,
+    pub body: String,
+    pub description: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawSnippet {
+    prefix: OneOrMany,
+    body: OneOrMany,
+    #[serde(default)]
+    description: Option<String>,
+    // Archivos globales de VS Code: "cpp,c"
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+// Snippets cargados por lenguaje; un nombre repetido reemplaza al anterior
+static SNIPPET_PACKS: Lazy<Mutex<HashMap<String, Vec<SnippetDefinition>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Carga un paquete `{"nombre": {"prefix", "body", "description"}}` para `language`
+/// (o para los lenguajes de su `scope`). Devuelve cuántos snippets se han cargado.
+pub fn load_snippet_pack(language: &str, json: &str) -> RunixResult<usize> {
+    let raw: BTreeMap<String, RawSnippet> = serde_json::from_str(json)
+        .map_err(|e| RunixError::InvalidArgument(format!("invalid snippet pack: {}", e)))?;
+    let count = raw.len();
+
+    let mut packs = SNIPPET_PACKS.lock_or_recover();
+    for (name, snippet) in raw {
+        let languages: Vec<String> = match &snippet.scope {
+            Some(scope) => scope.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+            None => vec![language.to_string()],
+        };
+        let definition = SnippetDefinition {
+            name: name.clone(),
+            prefixes: snippet.prefix.into_vec(),
+            body: snippet.body.into_vec().join("\n"),
+            description: snippet.description.unwrap_or_default(),
+        };
+        for language in languages {
+            let snippets = packs.entry(language).or_default();
+            snippets.retain(|s| s.name != name);
+            snippets.push(definition.clone());
+        }
+    }
+    Ok(count)
+}
+
+/// Snippets de `language` cuyo prefijo encaja con `typed`, mejor puntuados primero.
+pub fn snippet_suggestions(language: &str, typed: &str) -> Vec<SnippetDefinition> {
+    if typed.is_empty() {
+        return Vec::new();
+    }
+    let packs = SNIPPET_PACKS.lock_or_recover();
+    let mut scored: Vec<(i32, SnippetDefinition)> = packs.get(language)
+        .into_iter()
+        .flatten()
+        .filter_map(|snippet| {
+            let score = snippet.prefixes.iter().filter_map(|prefix| fuzzy_score(typed, prefix)).max()?;
+            Some((score, snippet.clone()))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.name.cmp(&b.1.name)));
+    scored.into_iter().map(|(_, snippet)| snippet).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slices(expanded: &ExpandedSnippet, index: u32) -> Vec<&str> {
+        let tab_stop = expanded.tab_stops.iter().find(|t| t.index == index).unwrap();
+        tab_stop.ranges.iter().map(|&(s, e)| &expanded.text[s..e]).collect()
+    }
+
+    #[test]
+    fn expands_tab_stops_mirrors_and_choices() {
+        let nodes = parse_snippet("for (${1|int,long long|} ${2:i} = 0; $2 < ${3:n}; ++$2) {\n\t$0\n}");
+        let context = SnippetContext { indent: "  ".to_string(), ..SnippetContext::default() };
+        let expanded = expand_snippet(&nodes, &context);
+
+        assert_eq!(expanded.text, "for (int i = 0; i < n; ++i) {\n  \t\n  }");
+        let order: Vec<u32> = expanded.tab_stops.iter().map(|t| t.index).collect();
+        assert_eq!(order, vec![1, 2, 3, 0]);
+        assert_eq!(slices(&expanded, 2), vec!["i", "i", "i"]);
+        assert_eq!(expanded.tab_stops[0].choices.as_deref(), Some(&["int".to_string(), "long long".to_string()][..]));
+    }
+
+    #[test]
+    fn resolves_variables_and_transforms() {
+        let mut context = SnippetContext::default();
+        context.variables.insert("TM_FILENAME".to_string(), "segment_tree.cpp".to_string());
+        let nodes = parse_snippet("struct ${TM_FILENAME_BASE/(.*)/${1:/pascalcase}/} {} // ${1:x} ${1/(.)/${1:/upcase}/} \\$ $UNKNOWN ${2:a ${3:b}}");
+        let expanded = expand_snippet(&nodes, &context);
+
+        assert_eq!(expanded.text, "struct SegmentTree {} // x X $ UNKNOWN a b");
+        assert_eq!(slices(&expanded, 2), vec!["a b"]);
+        assert_eq!(slices(&expanded, 3), vec!["b"]);
+        // La variable desconocida pasa a ser un placeholder tras los índices numerados
+        assert_eq!(slices(&expanded, 4), vec!["UNKNOWN"]);
+        assert_eq!(expanded.tab_stops.last().unwrap().ranges, vec![(expanded.text.len(), expanded.text.len())]);
+    }
+
+    #[test]
+    fn loads_packs_and_suggests_by_prefix() {
+        let pack = r#"{
+            "Fast IO": {"prefix": ["fastio", "io"], "body": ["ios::sync_with_stdio(false);", "cin.tie(nullptr);"]},
+            "For loop": {"prefix": "fori", "body": "for (int ${1:i} = 0; $1 < ${2:n}; ++$1) {$0}", "description": "Index loop", "scope": "cpp,c"}
+        }"#;
+        assert_eq!(load_snippet_pack("cpp", pack).unwrap(), 2);
+        let suggestions = snippet_suggestions("cpp", "fi");
+        assert_eq!(suggestions[0].name, "For loop");
+        assert_eq!(snippet_suggestions("c", "fori").len(), 1);
+        assert_eq!(snippet_suggestions("cpp", "fastio")[0].body, "ios::sync_with_stdio(false);\ncin.tie(nullptr);");
+        assert!(load_snippet_pack("cpp", "[1]").is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tree_sitter::{Language, Node, Query, QueryCursor, StreamingIterator, Tree};
+
+use crate::cache::LockExt;
+use crate::error::{RunixError, RunixResult};
+use crate::buffer::TextBuffer;
+use crate::offsets::{OffsetEncoding, TextPoint};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Namespace,
+    Class,
+    Struct,
+    Enum,
+    Function,
+    Method,
+    Type,
+    Variable,
+    Constant,
+    Macro,
+}
+
+impl SymbolKind {
+    fn from_capture(name: &str) -> Option<Self> {
+        match name.strip_prefix("definition.")? {
+            "namespace" | "module" => Some(SymbolKind::Namespace),
+            "class" | "interface" => Some(SymbolKind::Class),
+            "struct" => Some(SymbolKind::Struct),
+            "enum" => Some(SymbolKind::Enum),
+            "function" => Some(SymbolKind::Function),
+            "method" => Some(SymbolKind::Method),
+            "type" => Some(SymbolKind::Type),
+            "variable" => Some(SymbolKind::Variable),
+            "constant" => Some(SymbolKind::Constant),
+            "macro" => Some(SymbolKind::Macro),
+            _ => None,
+        }
+    }
+}
+
+/// Entrada del outline. `start..end` cubre la definición completa y `name_start..name_end`
+/// sólo el nombre, para colocar el cursor al saltar.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start: usize,
+    pub end: usize,
+    pub name_start: usize,
+    pub name_end: usize,
+    pub start_point: TextPoint,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<DocumentSymbol>,
+}
+
+// Queries de símbolos incluidas en la librería, una por lenguaje
+fn symbols_scm(language_name: &str) -> Option<&'static str> {
+    match language_name {
+        "cpp" => Some(include_str!("../queries/cpp/symbols.scm")),
+        "javascript" => Some(include_str!("../queries/javascript/symbols.scm")),
+        "python" => Some(include_str!("../queries/python/symbols.scm")),
+        _ => None,
+    }
+}
+
+static SYMBOL_QUERIES: Lazy<Mutex<HashMap<String, Arc<Query>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn symbols_query(language_name: &str, language: &Language) -> RunixResult<Arc<Query>> {
+    if let Some(query) = SYMBOL_QUERIES.lock_or_recover().get(language_name) {
+        return Ok(Arc::clone(query));
+    }
+    let source = symbols_scm(language_name)
+        .ok_or_else(|| RunixError::UnsupportedLanguage(format!("no symbols query for {}", language_name)))?;
+    let query = Arc::new(Query::new(language, source)?);
+    SYMBOL_QUERIES.lock_or_recover().insert(language_name.to_string(), Arc::clone(&query));
+    Ok(query)
+}
+
+/// Outline jerárquico del documento: cada símbolo cuelga del símbolo más interno que lo
+/// contiene, y las funciones dentro de clases o structs pasan a ser métodos.
+pub fn document_symbols(
+    tree: &Tree,
+    buffer: &TextBuffer,
+    language_name: &str,
+    language: &Language,
+    encoding: OffsetEncoding,
+) -> RunixResult<Vec<DocumentSymbol>> {
+    let query = symbols_query(language_name, language)?;
+    let capture_kinds: Vec<Option<SymbolKind>> = query.capture_names().iter()
+        .map(|name| SymbolKind::from_capture(name))
+        .collect();
+    let name_index = query.capture_index_for_name("name");
+
+    // Un mismo nodo puede encajar en varios patrones (función y variable en JS); gana el primero
+    let mut found: HashMap<(usize, usize), (usize, SymbolKind, tree_sitter::Range)> = HashMap::new();
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(&query, tree.root_node(), |node: Node| buffer.chunks(node.byte_range()));
+    while let Some(query_match) = matches.next() {
+        let definition = query_match.captures.iter()
+            .find_map(|c| capture_kinds[c.index as usize].map(|kind| (c.node, kind)));
+        let name = query_match.captures.iter().find(|c| Some(c.index) == name_index);
+        let (Some((node, kind)), Some(name)) = (definition, name) else {
+            continue;
+        };
+        let key = (node.start_byte(), node.end_byte());
+        let entry = (query_match.pattern_index, kind, name.node.range());
+        match found.get(&key) {
+            Some(existing) if existing.0 <= entry.0 => {}
+            _ => { found.insert(key, entry); }
+        }
+    }
+
+    let mut flat: Vec<_> = found.into_iter().collect();
+    // Por inicio y, a igual inicio, el más externo primero
+    flat.sort_by(|a, b| a.0.0.cmp(&b.0.0).then(b.0.1.cmp(&a.0.1)));
+
+    let mut roots = Vec::new();
+    let mut stack: Vec<DocumentSymbol> = Vec::new();
+    for ((start, end), (_, kind, name_range)) in flat {
+        let name = buffer.slice(name_range.start_byte..name_range.end_byte).split_whitespace().collect::<String>();
+        let mut symbol = DocumentSymbol {
+            name,
+            kind,
+            start: buffer.convert(start, encoding),
+            end: buffer.convert(end, encoding),
+            name_start: buffer.convert(name_range.start_byte, encoding),
+            name_end: buffer.convert(name_range.end_byte, encoding),
+            start_point: buffer.byte_to_point(start, encoding),
+            children: Vec::new(),
+        };
+        while stack.last().is_some_and(|top| top.end < symbol.end || top.start > symbol.start) {
+            let done = stack.pop().unwrap();
+            attach(&mut stack, &mut roots, done);
+        }
+        if symbol.kind == SymbolKind::Function
+            && stack.last().is_some_and(|parent| matches!(parent.kind, SymbolKind::Class | SymbolKind::Struct)) {
+            symbol.kind = SymbolKind::Method;
+        }
+        stack.push(symbol);
+    }
+    while let Some(done) = stack.pop() {
+        attach(&mut stack, &mut roots, done);
+    }
+    Ok(roots)
+}
+
+fn attach(stack: &mut [DocumentSymbol], roots: &mut Vec<DocumentSymbol>, symbol: DocumentSymbol) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(symbol),
+        None => roots.push(symbol),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::highlighting::load_language;
+    use tree_sitter::Parser;
+
+    fn outline(language_name: &str, code: &str) -> Vec<DocumentSymbol> {
+        let language = load_language(language_name).unwrap();
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(code, None).unwrap();
+        document_symbols(&tree, &TextBuffer::new(code), language_name, &language, OffsetEncoding::Utf8).unwrap()
+    }
+
+    fn names(symbols: &[DocumentSymbol]) -> Vec<(&str, SymbolKind)> {
+        symbols.iter().map(|s| (s.name.as_str(), s.kind)).collect()
+    }
+
+    #[test]
+    fn builds_cpp_outline() {
+        let code = "#define MOD 1000000007\n\
+            const int N = 1e5;\n\
+            struct segtree {\n  int t[4 * N];\n  void update(int v, int x) {}\n  int& at(int i) { return t[i]; }\n};\n\
+            void segtree_build(segtree& s) {}\n\
+            int main() { int local = 0; }\n";
+        let symbols = outline("cpp", code);
+        assert_eq!(names(&symbols), vec![
+            ("MOD", SymbolKind::Constant),
+            ("N", SymbolKind::Variable),
+            ("segtree", SymbolKind::Struct),
+            ("segtree_build", SymbolKind::Function),
+            ("main", SymbolKind::Function),
+        ]);
+        assert_eq!(names(&symbols[2].children), vec![("update", SymbolKind::Method), ("at", SymbolKind::Method)]);
+        assert!(symbols[4].children.is_empty());
+    }
+
+    #[test]
+    fn builds_python_and_javascript_outlines() {
+        let symbols = outline("python", "LIMIT = 10\nclass Graph:\n    def dfs(self, u):\n        pass\ndef solve():\n    pass\n");
+        assert_eq!(names(&symbols), vec![("LIMIT", SymbolKind::Variable), ("Graph", SymbolKind::Class), ("solve", SymbolKind::Function)]);
+        assert_eq!(names(&symbols[1].children), vec![("dfs", SymbolKind::Method)]);
+
+        let symbols = outline("javascript", "const add = (a, b) => a + b;\nlet count = 0;\nclass A { run() {} }\n");
+        assert_eq!(names(&symbols), vec![("add", SymbolKind::Function), ("count", SymbolKind::Variable), ("A", SymbolKind::Class)]);
+        assert_eq!(names(&symbols[2].children), vec![("run", SymbolKind::Method)]);
+    }
+}
@@ -0,0 +1,113 @@
+use serde::Serialize;
+use tree_sitter::{Node, Tree};
+
+use crate::buffer::TextBuffer;
+use crate::offsets::{OffsetEncoding, TextPoint};
+
+// Longitud máxima del fragmento citado en los mensajes
+const MAX_SNIPPET_CHARS: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyntaxErrorKind {
+    /// Token que el parser ha insertado para poder continuar (`MISSING`)
+    Missing,
+    /// Texto que el parser no ha podido encajar (`ERROR`)
+    Unexpected,
+}
+
+/// Error de sintaxis con offsets y columnas en la codificación pedida.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyntaxDiagnostic {
+    pub kind: SyntaxErrorKind,
+    pub start: usize,
+    pub end: usize,
+    pub start_point: TextPoint,
+    pub end_point: TextPoint,
+    pub message: String,
+}
+
+/// Recorre sólo las ramas con `has_error()` y reporta cada nodo `ERROR` (sin entrar en él)
+/// y cada nodo `MISSING`, en orden de aparición.
+pub fn syntax_diagnostics(tree: &Tree, buffer: &TextBuffer, encoding: OffsetEncoding) -> Vec<SyntaxDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut stack = vec![tree.root_node()];
+
+    while let Some(node) = stack.pop() {
+        let kind = if node.is_missing() {
+            SyntaxErrorKind::Missing
+        } else if node.is_error() {
+            SyntaxErrorKind::Unexpected
+        } else {
+            if node.has_error() {
+                let mut cursor = node.walk();
+                let children: Vec<Node> = node.children(&mut cursor).collect();
+                stack.extend(children.into_iter().rev());
+            }
+            continue;
+        };
+
+        let message = match kind {
+            SyntaxErrorKind::Missing if node.is_named() => format!("missing {}", node.kind().replace('_', " ")),
+            SyntaxErrorKind::Missing => format!("missing `{}`", node.kind()),
+            SyntaxErrorKind::Unexpected => match first_token(node, buffer) {
+                Some(token) => format!("unexpected `{}`", token),
+                None => "syntax error".to_string(),
+            },
+        };
+        diagnostics.push(SyntaxDiagnostic {
+            kind,
+            start: buffer.convert(node.start_byte(), encoding),
+            end: buffer.convert(node.end_byte(), encoding),
+            start_point: buffer.byte_to_point(node.start_byte(), encoding),
+            end_point: buffer.byte_to_point(node.end_byte(), encoding),
+            message,
+        });
+    }
+    diagnostics
+}
+
+// Texto de la primera hoja no vacía del nodo, recortado a una línea
+fn first_token(node: Node, buffer: &TextBuffer) -> Option<String> {
+    let mut leaf = node;
+    while let Some(child) = (0..leaf.child_count()).filter_map(|i| leaf.child(i)).find(|c| c.end_byte() > c.start_byte()) {
+        leaf = child;
+    }
+    let leaf = buffer.slice(leaf.byte_range());
+    let token = leaf.lines().next()?.trim();
+    if token.is_empty() {
+        return None;
+    }
+    match token.char_indices().nth(MAX_SNIPPET_CHARS) {
+        Some((cut, _)) => Some(format!("{}…", &token[..cut])),
+        None => Some(token.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::highlighting::load_language;
+    use tree_sitter::Parser;
+
+    fn parse(code: &str) -> Tree {
+        let mut parser = Parser::new();
+        parser.set_language(&load_language("cpp").unwrap()).unwrap();
+        parser.parse(code, None).unwrap()
+    }
+
+    #[test]
+    fn reports_missing_and_unexpected_tokens() {
+        let code = "int main() {\n  int x = 1\n  return x;\n}\n}\n";
+        let diagnostics = syntax_diagnostics(&parse(code), &TextBuffer::new(code), OffsetEncoding::Utf16);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec!["missing `;`", "unexpected `}`"]);
+
+        assert_eq!(diagnostics[0].kind, SyntaxErrorKind::Missing);
+        assert_eq!(diagnostics[0].start_point, TextPoint { row: 1, column: 11 });
+        assert_eq!(diagnostics[1].start_point, TextPoint { row: 4, column: 0 });
+
+        let valid = "int main() { return 0; }";
+        assert!(syntax_diagnostics(&parse(valid), &TextBuffer::new(valid), OffsetEncoding::Utf8).is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tree_sitter::{Node, Parser, Tree};
+
+use crate::buffer::TextBuffer;
+use crate::cache::LockExt;
+use crate::document::Document;
+use crate::error::{RunixError, RunixResult};
+use crate::offsets::OffsetEncoding;
+use crate::symbols::document_symbols;
+use crate::types::TextEdit;
+
+/// Plantilla de la librería (DSU, segment tree, Dijkstra...). Los metadatos van en
+/// comentarios de cabecera:
+///
+/// ```text
+/// // @name dijkstra
+/// // @description Caminos mínimos con heap
+/// // @requires graph
+/// ```
+///
+/// Las líneas `#include`, `using namespace` e `import` del cuerpo se separan en `headers`
+/// para añadirlas arriba del archivo en lugar de donde se inserta la plantilla.
+#[derive(Debug, Clone, Serialize)]
+pub struct Template {
+    pub name: String,
+    pub language: String,
+    pub description: String,
+    pub requires: Vec<String>,
+    pub headers: Vec<String>,
+    #[serde(skip_serializing)]
+    pub body: String,
+}
+
+/// Resultado de insertar una plantilla: los edits a aplicar y qué se ha añadido.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateInsertion {
+    pub edits: Vec<TextEdit>,
+    /// Plantillas insertadas, dependencias primero
+    pub templates: Vec<String>,
+    pub headers: Vec<String>,
+}
+
+// Plantillas por lenguaje y nombre
+static TEMPLATES: Lazy<Mutex<HashMap<String, BTreeMap<String, Template>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn language_for_extension(extension: &str) -> Option<&'static str> {
+    match extension {
+        "cpp" | "cc" | "cxx" | "hpp" | "h" => Some("cpp"),
+        "py" => Some("python"),
+        "js" | "mjs" => Some("javascript"),
+        _ => None,
+    }
+}
+
+fn comment_prefix(language: &str) -> &'static str {
+    match language {
+        "python" => "#",
+        _ => "//",
+    }
+}
+
+fn is_header_line(language: &str, line: &str) -> bool {
+    let line = line.trim();
+    match language {
+        "cpp" => line.starts_with("#include") || line.starts_with("using namespace"),
+        "python" => line.starts_with("import ") || (line.starts_with("from ") && line.contains(" import ")),
+        "javascript" => line.starts_with("import ") || (line.contains("require(") && !line.starts_with("//")),
+        _ => false,
+    }
+}
+
+/// Parsea una plantilla; `name` se usa si la cabecera no trae `@name`.
+pub fn parse_template(language: &str, name: &str, source: &str) -> Template {
+    let comment = comment_prefix(language);
+    let mut template = Template {
+        name: name.to_string(),
+        language: language.to_string(),
+        description: String::new(),
+        requires: Vec::new(),
+        headers: Vec::new(),
+        body: String::new(),
+    };
+
+    let mut body = Vec::new();
+    for line in source.lines() {
+        let metadata = line.trim().strip_prefix(comment).map(str::trim).and_then(|rest| rest.strip_prefix('@'));
+        if let Some((key, value)) = metadata.map(|m| m.split_once(char::is_whitespace).unwrap_or((m, ""))) {
+            let value = value.trim();
+            match key {
+                "name" => template.name = value.to_string(),
+                "description" => template.description = value.to_string(),
+                "requires" => template.requires.extend(value.split([',', ' ']).filter(|s| !s.is_empty()).map(str::to_string)),
+                _ => body.push(line),
+            }
+            continue;
+        }
+        if is_header_line(language, line) {
+            template.headers.push(line.trim().to_string());
+        } else {
+            body.push(line);
+        }
+    }
+    template.body = body.join("\n").trim_matches('\n').to_string();
+    template
+}
+
+pub fn register_template(template: Template) {
+    TEMPLATES.lock_or_recover()
+        .entry(template.language.clone())
+        .or_default()
+        .insert(template.name.clone(), template);
+}
+
+/// Indexa los archivos de `directory/<lenguaje>/` o, si están sueltos, los de `directory`
+/// según su extensión. Devuelve las plantillas encontradas.
+pub fn index_template_dir(directory: &Path) -> RunixResult<Vec<Template>> {
+    let mut found = Vec::new();
+    let mut pending = vec![directory.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| RunixError::InvalidArgument(format!("cannot read {}: {}", dir.display(), e)))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let Some(language) = path.extension().and_then(|e| e.to_str()).and_then(language_for_extension) else {
+                continue;
+            };
+            let Ok(source) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+            let template = parse_template(language, name, &source);
+            register_template(template.clone());
+            found.push(template);
+        }
+    }
+    found.sort_by(|a, b| (&a.language, &a.name).cmp(&(&b.language, &b.name)));
+    Ok(found)
+}
+
+pub fn list_templates(language: &str) -> Vec<Template> {
+    TEMPLATES.lock_or_recover().get(language).map(|t| t.values().cloned().collect()).unwrap_or_default()
+}
+
+// Dependencias antes que quien las usa; error si falta alguna o hay un ciclo
+fn resolve_order(templates: &BTreeMap<String, Template>, name: &str) -> RunixResult<Vec<Template>> {
+    fn visit(
+        templates: &BTreeMap<String, Template>,
+        name: &str,
+        visiting: &mut HashSet<String>,
+        done: &mut HashSet<String>,
+        order: &mut Vec<Template>,
+    ) -> RunixResult<()> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if !visiting.insert(name.to_string()) {
+            return Err(RunixError::InvalidArgument(format!("template dependency cycle at '{}'", name)));
+        }
+        let template = templates.get(name)
+            .ok_or_else(|| RunixError::InvalidArgument(format!("unknown template '{}'", name)))?;
+        for dependency in &template.requires {
+            visit(templates, dependency, visiting, done, order)?;
+        }
+        visiting.remove(name);
+        done.insert(name.to_string());
+        order.push(template.clone());
+        Ok(())
+    }
+
+    let mut order = Vec::new();
+    visit(templates, name, &mut HashSet::new(), &mut HashSet::new(), &mut order)?;
+    Ok(order)
+}
+
+fn normalize(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Nodos de primer nivel que cuentan como cabecera del archivo
+fn is_header_node(language: &str, kind: &str) -> bool {
+    match language {
+        "cpp" => matches!(kind, "preproc_include" | "using_declaration"),
+        "python" => matches!(kind, "import_statement" | "import_from_statement" | "future_import_statement"),
+        "javascript" => kind == "import_statement",
+        _ => false,
+    }
+}
+
+// Lo que una pieza de código define a primer nivel: los nombres de sus símbolos raíz
+// (funciones, clases, globales...) y el texto normalizado de cada nodo de primer nivel que
+// no sea cabecera ni comentario, para las plantillas que no definen nada con nombre
+struct TopLevel {
+    names: HashSet<String>,
+    nodes: HashSet<String>,
+}
+
+impl TopLevel {
+    fn of(document: &Document, tree: &Tree, buffer: &TextBuffer) -> Self {
+        let language = document.language_name.as_str();
+        let names = document_symbols(tree, buffer, language, &document.language, OffsetEncoding::Utf8)
+            .map(|symbols| symbols.into_iter().map(|symbol| symbol.name).collect())
+            .unwrap_or_default();
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let nodes = root.children(&mut cursor)
+            .filter(|node: &Node| !is_header_node(language, node.kind()) && node.kind() != "comment")
+            .map(|node| normalize(&buffer.slice(node.byte_range())))
+            .collect();
+        Self { names, nodes }
+    }
+
+    // La plantilla ya está si el documento define todos sus nombres o, si no declara
+    // ninguno, contiene todos sus nodos
+    fn contains(&self, template: &TopLevel) -> bool {
+        if template.names.is_empty() {
+            template.nodes.is_subset(&self.nodes)
+        } else {
+            template.names.is_subset(&self.names)
+        }
+    }
+}
+
+/// Inserta la plantilla `name` y sus dependencias que aún no estén en el documento. El código
+/// va antes de la declaración de primer nivel que contiene `cursor` (nunca dentro de una
+/// función) y las cabeceras que falten, tras las existentes.
+pub fn insert_template(document: &Document, name: &str, cursor: usize) -> RunixResult<TemplateInsertion> {
+    let order = {
+        let templates = TEMPLATES.lock_or_recover();
+        let templates = templates.get(&document.language_name)
+            .ok_or_else(|| RunixError::InvalidArgument(format!("no templates for {}", document.language_name)))?;
+        resolve_order(templates, name)?
+    };
+    let buffer = document.buffer();
+    let language = document.language_name.as_str();
+
+    // Presencia según lo que el árbol define a primer nivel, no por coincidencias de texto
+    let defined = TopLevel::of(document, &document.tree, buffer);
+    let mut parser = Parser::new();
+    parser.set_language(&document.language)
+        .map_err(|e| RunixError::UnsupportedLanguage(format!("{}: {}", language, e)))?;
+    let mut missing: Vec<&Template> = Vec::new();
+    for template in &order {
+        let tree = parser.parse(&template.body, None)
+            .ok_or_else(|| RunixError::Parse(format!("template '{}' could not be parsed", template.name)))?;
+        if !defined.contains(&TopLevel::of(document, &tree, &TextBuffer::new(&template.body))) {
+            missing.push(template);
+        }
+    }
+
+    let root = document.tree.root_node();
+    let mut cursor_walk = root.walk();
+    let top_level: Vec<_> = root.children(&mut cursor_walk).collect();
+    let existing_headers: HashSet<String> = top_level.iter()
+        .filter(|node| is_header_node(language, node.kind()))
+        .map(|node| normalize(&buffer.slice(node.byte_range())))
+        .collect();
+
+    let mut headers: Vec<String> = Vec::new();
+    for template in &missing {
+        for header in &template.headers {
+            let normalized = normalize(header);
+            if !existing_headers.contains(&normalized) && !headers.iter().any(|h| normalize(h) == normalized) {
+                headers.push(header.clone());
+            }
+        }
+    }
+    // `#include` antes que `using namespace`
+    headers.sort_by_key(|h| h.starts_with("using"));
+
+    let mut edits = Vec::new();
+    if !headers.is_empty() {
+        // Tras la línea de la última cabecera (preproc_include ya incluye su salto de línea)
+        let (position, prefix) = match top_level.iter().rfind(|n| is_header_node(language, n.kind())) {
+            Some(last) => {
+                let end = buffer.point_at(last.end_byte());
+                if end.column == 0 {
+                    (last.end_byte(), "")
+                } else if end.row + 1 < buffer.line_count() {
+                    (buffer.line_start(end.row + 1), "")
+                } else {
+                    (buffer.len(), "\n")
+                }
+            }
+            None => (0, ""),
+        };
+        let mut header_text = prefix.to_string();
+        for header in &headers {
+            header_text.push_str(header);
+            header_text.push('\n');
+        }
+        if position == 0 && !buffer.is_empty() {
+            header_text.push('\n');
+        }
+        edits.push(TextEdit::new(position, position, header_text));
+    }
+
+    if !missing.is_empty() {
+        let cursor = cursor.min(buffer.len());
+        let position = top_level.iter()
+            .find(|node| node.start_byte() < cursor && cursor < node.end_byte() && !is_header_node(language, node.kind()))
+            .map_or(cursor, |node| node.start_byte());
+        let mut body = missing.iter().map(|t| t.body.as_str()).collect::<Vec<_>>().join("\n\n");
+        body.push_str("\n\n");
+        edits.push(TextEdit::new(position, position, body));
+    }
+    edits.sort_by_key(|edit| edit.start);
+
+    Ok(TemplateInsertion {
+        edits,
+        templates: missing.iter().map(|t| t.name.clone()).collect(),
+        headers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::apply_edits;
+
+    #[test]
+    fn inserts_dependencies_and_missing_includes() {
+        register_template(parse_template("cpp", "graph", "#include <vector>\nusing namespace std;\n// @description Adjacency list\nvector<vector<pair<int,int>>> adj;\n"));
+        register_template(parse_template("cpp", "dijkstra_file", "// @name dijkstra\n// @requires graph\n#include <queue>\n#include <vector>\nvector<long long> dijkstra(int s) {\n  priority_queue<pair<long long,int>> pq;\n  return {};\n}\n"));
+        let dijkstra = &list_templates("cpp").into_iter().find(|t| t.name == "dijkstra").unwrap();
+        assert_eq!(dijkstra.requires, vec!["graph"]);
+        assert_eq!(dijkstra.headers, vec!["#include <queue>", "#include <vector>"]);
+
+        let code = "#include <vector>\n\nint main() {\n  \n}\n";
+        let document = Document::for_test("cpp", code, "");
+        let cursor = code.find("  \n").unwrap() + 2;
+
+        let insertion = insert_template(&document, "dijkstra", cursor).unwrap();
+        assert_eq!(insertion.templates, vec!["graph", "dijkstra"]);
+        assert_eq!(insertion.headers, vec!["#include <queue>", "using namespace std;"]);
+        let result = apply_edits(code, &insertion.edits);
+        assert!(result.starts_with("#include <vector>\n#include <queue>\nusing namespace std;\n\nvector<vector<pair<int,int>>> adj;\n\nvector<long long> dijkstra(int s) {"));
+        assert!(result.ends_with("}\n\nint main() {\n  \n}\n"));
+
+        assert!(matches!(insert_template(&document, "nope", 0), Err(RunixError::InvalidArgument(_))));
+
+        // Ya insertadas no se repiten, aunque el texto cambie de formato; la primera línea
+        // de la plantilla dentro de un comentario no cuenta como tenerla
+        let document = Document::for_test("cpp", &result.replace("adj;", "adj ;"), "");
+        assert!(insert_template(&document, "dijkstra", 0).unwrap().edits.is_empty());
+        let lookalike = "vector<vector<pair<int,int>>> adj;\n// vector<long long> dijkstra(int s) {\n";
+        let document = Document::for_test("cpp", lookalike, "");
+        assert_eq!(insert_template(&document, "dijkstra", 0).unwrap().templates, vec!["dijkstra"]);
+    }
+}
@@ -1,3 +1,8 @@
+use std::ops::Range;
+use similar::{DiffTag, TextDiff};
+
+use crate::types::TextEdit;
+
 // Maneja las diferencias carácter a carácter entre textos
 pub fn get_text_changes(old: &str, new: &str) -> Vec<(usize, usize)> {
     let mut changes = Vec::new();
@@ -72,8 +77,8 @@ pub fn text_difference_ratio(a: &str, b: &str) -> f64 {
     }
     
     // Contar caracteres restantes como diferencias
-    different += (a.len() as isize - i as isize).abs() as usize;
-    different += (b.len() as isize - j as isize).abs() as usize; // count remaining chars in b as well
+    different += (a.len() as isize - i as isize).unsigned_abs();
+    different += (b.len() as isize - j as isize).unsigned_abs(); // count remaining chars in b as well
     
     different as f64 / max_len
 }
@@ -93,4 +98,37 @@ pub fn get_reused_ranges(changed_ranges: &[(usize, usize)], text_len: usize) ->
         reused.push((last_end, text_len));
     }
     reused
-}
\ No newline at end of file
+}
+
+// Pares (rango en old, rango en new), en bytes, de las operaciones que no son iguales
+fn changed_ranges(diff: &TextDiff<'_, '_, '_, str>) -> Vec<(Range<usize>, Range<usize>)> {
+    let offsets = |slices: &[&str]| -> Vec<usize> {
+        std::iter::once(0).chain(slices.iter().scan(0, |offset, slice| {
+            *offset += slice.len();
+            Some(*offset)
+        })).collect()
+    };
+    let old_offsets = offsets(diff.old_slices());
+    let new_offsets = offsets(diff.new_slices());
+    diff.ops().iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| {
+            let (old, new) = (op.old_range(), op.new_range());
+            (old_offsets[old.start]..old_offsets[old.end], new_offsets[new.start]..new_offsets[new.end])
+        })
+        .collect()
+}
+
+/// Edits mínimos (offsets en bytes de `old`) que convierten `old` en `new`: diff por líneas
+/// y, dentro de cada bloque cambiado, por caracteres. Así un formateo que sólo reindenta no
+/// reescribe el resto de la línea y el cursor no salta.
+pub fn minimal_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    for (old_lines, new_lines) in changed_ranges(&TextDiff::from_lines(old, new)) {
+        let inserted = &new[new_lines.clone()];
+        for (removed, added) in changed_ranges(&TextDiff::from_chars(&old[old_lines.clone()], inserted)) {
+            edits.push(TextEdit::new(old_lines.start + removed.start, old_lines.start + removed.end, &inserted[added]));
+        }
+    }
+    edits
+}
@@ -3,23 +3,188 @@ use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
-use crate::cache::THEME_CACHE;
-#[derive(Debug, Serialize, Deserialize)]
+use log::error;
+use crate::cache::{LockExt, THEME_CACHE};
+
+/// Estilo de una captura tal como se escribe en el JSON del tema.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    #[serde(alias = "fg", alias = "color", skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<String>,
+    #[serde(alias = "bg", skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool
+}
+
+/// Una entrada del tema: un color suelto (`"keyword": "#C678DD"`) o un estilo completo.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ThemeEntry {
+    Color(String),
+    Style(Style)
+}
+
+impl ThemeEntry {
+    pub fn to_style(&self) -> Style {
+        match self {
+            ThemeEntry::Color(color) => Style { foreground: Some(color.clone()), ..Style::default() },
+            ThemeEntry::Style(style) => style.clone()
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Theme {
-    pub theme: HashMap<String, String>
+    pub theme: HashMap<String, ThemeEntry>,
+    // Colores base del editor, para el texto sin captura y el fondo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>
+}
+
+/// Estilo con los colores ya convertidos a ARGB (`0xAARRGGBB`), listo para Compose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct ResolvedStyle {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<u32>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool
+}
+
+impl ResolvedStyle {
+    // Bits de `flags` en la salida empaquetada
+    pub const BOLD: i32 = 1;
+    pub const ITALIC: i32 = 2;
+    pub const UNDERLINE: i32 = 4;
+    // El color empaquetado vale 0 cuando falta, igual que `#00000000`; estos bits lo distinguen
+    pub const HAS_FOREGROUND: i32 = 8;
+    pub const HAS_BACKGROUND: i32 = 16;
+
+    pub fn flags(&self) -> i32 {
+        let mut flags = 0;
+        if self.bold { flags |= Self::BOLD; }
+        if self.italic { flags |= Self::ITALIC; }
+        if self.underline { flags |= Self::UNDERLINE; }
+        if self.foreground.is_some() { flags |= Self::HAS_FOREGROUND; }
+        if self.background.is_some() { flags |= Self::HAS_BACKGROUND; }
+        flags
+    }
+}
+
+impl Style {
+    pub fn resolve(&self) -> ResolvedStyle {
+        ResolvedStyle {
+            foreground: self.foreground.as_deref().and_then(parse_color),
+            background: self.background.as_deref().and_then(parse_color),
+            bold: self.bold,
+            italic: self.italic,
+            underline: self.underline
+        }
+    }
+}
+
+/// Tabla de estilos deduplicada para una lista de nombres de resaltado.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StyleTable {
+    pub styles: Vec<ResolvedStyle>,
+    // Id de estilo para cada índice de `highlight_names`
+    pub highlight_styles: Vec<Option<usize>>
+}
+
+impl StyleTable {
+    pub fn style_for_highlight(&self, highlight_type: usize) -> Option<&ResolvedStyle> {
+        self.highlight_styles.get(highlight_type).copied().flatten().map(|id| &self.styles[id])
+    }
 }
 
 impl Theme {
+    /// Lee un tema en el formato `{"theme": {...}, "foreground": ..., "background": ...}` o
+    /// en el mapa plano `{"keyword": "#C678DD", ...}` que guarda el panel de temas.
+    pub fn from_json(json: &str) -> Result<Theme, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        if value.get("theme").is_some() {
+            return serde_json::from_value(value);
+        }
+        let mut theme = Theme {
+            theme: serde_json::from_value(value)?,
+            ..Theme::default()
+        };
+        theme.foreground = take_color(&mut theme.theme, "foreground");
+        theme.background = take_color(&mut theme.theme, "background");
+        Ok(theme)
+    }
+
     pub fn calculate_hash(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
         let mut keys: Vec<_> = self.theme.keys().collect();
         keys.sort();
         for key in keys {
             key.hash(&mut hasher);
-            self.theme.get(key).unwrap().hash(&mut hasher);
+            self.theme[key].hash(&mut hasher);
         }
+        self.foreground.hash(&mut hasher);
+        self.background.hash(&mut hasher);
         hasher.finish()
     }
+
+    /// Estilo de una captura con caída jerárquica: `function.builtin` → `function`.
+    pub fn style_for(&self, capture_name: &str) -> Option<Style> {
+        let mut name = capture_name;
+        loop {
+            if let Some(entry) = self.theme.get(name) {
+                return Some(entry.to_style());
+            }
+            name = &name[..name.rfind('.')?];
+        }
+    }
+
+    pub fn resolve_styles(&self, highlight_names: &[String]) -> StyleTable {
+        let mut table = StyleTable::default();
+        let mut ids: HashMap<ResolvedStyle, usize> = HashMap::new();
+        for name in highlight_names {
+            let id = self.style_for(name).map(|style| {
+                let resolved = style.resolve();
+                *ids.entry(resolved).or_insert_with(|| {
+                    table.styles.push(resolved);
+                    table.styles.len() - 1
+                })
+            });
+            table.highlight_styles.push(id);
+        }
+        table
+    }
+}
+
+// En el mapa plano, "foreground" y "background" son los colores base y no capturas
+fn take_color(entries: &mut HashMap<String, ThemeEntry>, key: &str) -> Option<String> {
+    match entries.remove(key)? {
+        ThemeEntry::Color(color) => Some(color),
+        ThemeEntry::Style(style) => style.foreground,
+    }
+}
+
+/// Convierte `#RGB`, `#RRGGBB` o `#AARRGGBB` (convención de Android) a ARGB.
+pub fn parse_color(value: &str) -> Option<u32> {
+    let hex = value.trim().strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        3 => {
+            let expanded: String = hex.chars().flat_map(|c| [c, c]).collect();
+            u32::from_str_radix(&expanded, 16).ok().map(|rgb| 0xFF00_0000 | rgb)
+        }
+        6 => u32::from_str_radix(hex, 16).ok().map(|rgb| 0xFF00_0000 | rgb),
+        8 => u32::from_str_radix(hex, 16).ok(),
+        _ => None
+    }
 }
 
 pub fn get_cached_theme(theme_str: &str) -> Arc<Theme> {
@@ -27,15 +192,53 @@ pub fn get_cached_theme(theme_str: &str) -> Arc<Theme> {
     theme_str.hash(&mut hasher);
     let input_hash = hasher.finish();
     
-    if let Some(cached_theme) = THEME_CACHE.lock().unwrap().get(&input_hash) {
+    if let Some(cached_theme) = THEME_CACHE.lock_or_recover().get(&input_hash) {
         return Arc::clone(cached_theme);
     }
     
-    let theme: Theme = serde_json::from_str(theme_str).unwrap_or(Theme { 
-        theme: HashMap::new()
+    // Un tema inválido deja el código sin colores; validateTheme da el detalle
+    let theme = Theme::from_json(theme_str).unwrap_or_else(|e| {
+        error!("Invalid theme JSON, using empty theme: {}", e);
+        Theme::default()
     });
     
     let theme_arc = Arc::new(theme);
-    THEME_CACHE.lock().unwrap().insert(input_hash, Arc::clone(&theme_arc));
+    THEME_CACHE.lock_or_recover().insert(input_hash, Arc::clone(&theme_arc));
     theme_arc
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn style_falls_back_to_parent_capture() {
+        let theme: Theme = serde_json::from_str(r##"{"theme": {
+            "function": "#61AFEF",
+            "keyword": {"foreground": "#C678DD", "bold": true}
+        }}"##).unwrap();
+
+        let style = theme.style_for("function.builtin").unwrap();
+        assert_eq!(style.foreground.as_deref(), Some("#61AFEF"));
+        assert!(theme.style_for("keyword.return").unwrap().bold);
+        assert!(theme.style_for("variable").is_none());
+
+        let names = vec!["keyword".to_string(), "function.builtin".to_string(), "function".to_string()];
+        let table = theme.resolve_styles(&names);
+        assert_eq!(table.styles.len(), 2);
+        assert_eq!(table.highlight_styles, vec![Some(0), Some(1), Some(1)]);
+        assert_eq!(table.styles[1].foreground, Some(0xFF61AFEF));
+    }
+
+    #[test]
+    fn flags_mark_transparent_colors_as_defined() {
+        let theme: Theme = serde_json::from_str(r##"{"theme": {
+            "comment": {"foreground": "#00000000", "italic": true}
+        }}"##).unwrap();
+        let table = theme.resolve_styles(&["comment".to_string()]);
+        let style = table.style_for_highlight(0).unwrap();
+        assert_eq!(style.foreground, Some(0));
+        assert_eq!(style.flags(), ResolvedStyle::ITALIC | ResolvedStyle::HAS_FOREGROUND);
+        assert_eq!(ResolvedStyle::default().flags(), 0);
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::error::{RunixError, RunixResult};
+use crate::theme::{parse_color, Style, Theme, ThemeEntry};
+
+/// Formatos de tema externos que se pueden convertir al modelo de `Theme`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeFormat {
+    /// JSON de VS Code (`colors` + `tokenColors`), admite comentarios y comas finales
+    VsCode,
+    /// `.tmTheme` de TextMate/Sublime (plist XML)
+    TextMate,
+    /// TOML de Helix
+    Helix,
+}
+
+impl ThemeFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vscode" | "json" => Some(ThemeFormat::VsCode),
+            "textmate" | "tmtheme" | "plist" => Some(ThemeFormat::TextMate),
+            "helix" | "toml" => Some(ThemeFormat::Helix),
+            _ => None,
+        }
+    }
+
+    /// Deduce el formato a partir del contenido.
+    pub fn detect(source: &str) -> Self {
+        let trimmed = source.trim_start();
+        if trimmed.starts_with('<') {
+            ThemeFormat::TextMate
+        } else if trimmed.starts_with('{') || trimmed.starts_with("//") || trimmed.starts_with("/*") {
+            ThemeFormat::VsCode
+        } else {
+            ThemeFormat::Helix
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedTheme {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub theme: Theme,
+    /// Scopes o claves del tema original sin equivalente en capturas de tree-sitter
+    pub unknown_scopes: Vec<String>,
+    /// Valores que no se pudieron convertir (colores con nombre, estilos no soportados...)
+    pub warnings: Vec<String>,
+}
+
+/// Scopes de TextMate y la captura de tree-sitter equivalente. Se elige la entrada con el
+/// prefijo más largo, así `support.function` gana a `support`.
+pub const SCOPE_MAPPINGS: &[(&str, &str)] = &[
+    ("comment", "comment"),
+    ("comment.block.documentation", "comment.documentation"),
+    ("punctuation.definition.comment", "comment"),
+    ("string", "string"),
+    ("string.regexp", "string.regexp"),
+    ("string.quoted.other.lt-gt.include", "string"),
+    ("constant.character.escape", "string.escape"),
+    ("constant.character", "character"),
+    ("constant.numeric", "number"),
+    ("constant.language", "constant.builtin"),
+    ("constant.language.boolean", "boolean"),
+    ("constant.other", "constant"),
+    ("constant", "constant"),
+    ("keyword", "keyword"),
+    ("keyword.control", "keyword"),
+    ("keyword.control.import", "keyword.import"),
+    ("keyword.control.directive", "keyword.directive"),
+    ("keyword.control.return", "keyword.return"),
+    ("keyword.operator", "operator"),
+    ("keyword.operator.new", "keyword"),
+    ("keyword.other", "keyword"),
+    ("storage", "keyword"),
+    ("storage.type", "type"),
+    ("storage.type.function", "keyword.function"),
+    ("storage.modifier", "keyword.modifier"),
+    ("meta.preprocessor", "keyword.directive"),
+    ("entity.name.function", "function"),
+    ("entity.name.function.member", "function.method"),
+    ("meta.function-call", "function.call"),
+    ("support.function", "function.builtin"),
+    ("entity.name.type", "type"),
+    ("entity.name.class", "type"),
+    ("entity.name.struct", "type"),
+    ("entity.name.namespace", "module"),
+    ("entity.name.tag", "tag"),
+    ("entity.name.label", "label"),
+    ("entity.other.attribute-name", "attribute"),
+    ("entity.other.inherited-class", "type"),
+    ("support.type", "type.builtin"),
+    ("support.class", "type"),
+    ("support.constant", "constant.builtin"),
+    ("support.variable", "variable.builtin"),
+    ("variable", "variable"),
+    ("variable.parameter", "variable.parameter"),
+    ("variable.language", "variable.builtin"),
+    ("variable.other.constant", "constant"),
+    ("variable.other.member", "property"),
+    ("variable.other.property", "property"),
+    ("variable.other.object.property", "property"),
+    ("punctuation", "punctuation"),
+    ("punctuation.definition.string", "string"),
+    ("punctuation.separator", "punctuation.delimiter"),
+    ("punctuation.terminator", "punctuation.delimiter"),
+    ("punctuation.section", "punctuation.bracket"),
+    ("meta.brace", "punctuation.bracket"),
+];
+
+/// Familias de capturas de las queries incluidas (nomenclatura de nvim-treesitter). Una clave
+/// de Helix fuera de ellas, como `namespace` o `special`, no coloreará nada.
+pub const CAPTURE_FAMILIES: &[&str] = &[
+    "attribute", "boolean", "character", "comment", "constant", "constructor", "function",
+    "keyword", "label", "module", "number", "operator", "property", "punctuation", "string",
+    "tag", "type", "variable",
+];
+
+/// Captura equivalente a un scope de TextMate, o `None` si el scope no está en la tabla.
+pub fn map_textmate_scope(scope: &str) -> Option<&'static str> {
+    SCOPE_MAPPINGS.iter()
+        .filter(|(prefix, _)| scope == *prefix || scope.starts_with(&format!("{}.", prefix)))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, capture)| *capture)
+}
+
+pub fn import_theme(source: &str, format: Option<ThemeFormat>) -> RunixResult<ImportedTheme> {
+    match format.unwrap_or_else(|| ThemeFormat::detect(source)) {
+        ThemeFormat::VsCode => import_vscode(source),
+        ThemeFormat::TextMate => import_textmate(source),
+        ThemeFormat::Helix => import_helix(source),
+    }
+}
+
+// Acumula las reglas de un tema basado en scopes de TextMate
+#[derive(Default)]
+struct ScopeImporter {
+    theme: Theme,
+    unknown: BTreeSet<String>,
+    warnings: Vec<String>,
+}
+
+impl ScopeImporter {
+    fn add_rule(&mut self, scope_selector: &str, foreground: Option<&str>, background: Option<&str>, font_style: Option<&str>) {
+        let foreground = foreground.and_then(|c| self.color(c));
+        let background = background.and_then(|c| self.color(c));
+        let font_style = font_style.unwrap_or("");
+        let style = Style {
+            foreground,
+            background,
+            bold: font_style.contains("bold"),
+            italic: font_style.contains("italic"),
+            underline: font_style.contains("underline"),
+        };
+        if style == Style::default() {
+            return;
+        }
+
+        for selector in scope_selector.split(',') {
+            // En selectores descendientes ("meta.function entity.name") cuenta el último scope;
+            // las exclusiones ("- comment") se ignoran
+            let Some(scope) = selector.split(" - ").next()
+                .and_then(|s| s.split_whitespace().last()) else {
+                continue;
+            };
+            match map_textmate_scope(scope) {
+                // Las reglas más específicas no pisan a una ya asignada a la misma captura
+                Some(capture) => {
+                    let exact = SCOPE_MAPPINGS.iter().any(|(prefix, _)| *prefix == scope);
+                    if exact || !self.theme.theme.contains_key(capture) {
+                        self.theme.theme.insert(capture.to_string(), ThemeEntry::Style(style.clone()));
+                    }
+                }
+                None => {
+                    self.unknown.insert(scope.to_string());
+                }
+            }
+        }
+    }
+
+    fn color(&mut self, value: &str) -> Option<String> {
+        let normalized = css_to_android_color(value);
+        if normalized.is_none() {
+            self.warnings.push(format!("unsupported color '{}'", value));
+        }
+        normalized
+    }
+
+    fn finish(mut self, name: Option<String>) -> ImportedTheme {
+        // Los colores sueltos se guardan como cadena, igual que los temas escritos a mano
+        for entry in self.theme.theme.values_mut() {
+            if let ThemeEntry::Style(style) = entry {
+                if style.background.is_none() && !style.bold && !style.italic && !style.underline {
+                    if let Some(fg) = style.foreground.take() {
+                        *entry = ThemeEntry::Color(fg);
+                    }
+                }
+            }
+        }
+        ImportedTheme {
+            name,
+            theme: self.theme,
+            unknown_scopes: self.unknown.into_iter().collect(),
+            warnings: self.warnings,
+        }
+    }
+}
+
+/// Convierte colores CSS (`#RGB`, `#RRGGBB`, `#RRGGBBAA`) al formato `#AARRGGBB` de Android.
+pub fn css_to_android_color(value: &str) -> Option<String> {
+    let hex = value.trim().strip_prefix('#')?;
+    // Sin esto, un carácter multibyte haría que los cortes por byte de abajo entren en pánico
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let android = match hex.len() {
+        8 => format!("#{}{}", &hex[6..8], &hex[0..6]),
+        4 => {
+            let expanded: String = hex.chars().flat_map(|c| [c, c]).collect();
+            format!("#{}{}", &expanded[6..8], &expanded[0..6])
+        }
+        _ => format!("#{}", hex),
+    };
+    parse_color(&android).map(|_| android.to_uppercase())
+}
+
+fn import_vscode(source: &str) -> RunixResult<ImportedTheme> {
+    let json: JsonValue = serde_json::from_str(&strip_jsonc(source))
+        .map_err(|e| RunixError::InvalidArgument(format!("VS Code theme: {}", e)))?;
+
+    let mut importer = ScopeImporter::default();
+    if let Some(colors) = json.get("colors") {
+        let fg = colors.get("editor.foreground").and_then(JsonValue::as_str);
+        let bg = colors.get("editor.background").and_then(JsonValue::as_str);
+        importer.theme.foreground = fg.and_then(|c| importer.color(c));
+        importer.theme.background = bg.and_then(|c| importer.color(c));
+    }
+
+    for rule in json.get("tokenColors").and_then(JsonValue::as_array).into_iter().flatten() {
+        let settings = rule.get("settings");
+        let get = |key: &str| settings.and_then(|s| s.get(key)).and_then(JsonValue::as_str);
+        let scopes: Vec<String> = match rule.get("scope") {
+            Some(JsonValue::String(s)) => vec![s.clone()],
+            Some(JsonValue::Array(items)) => items.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            // Sin scope: colores globales al estilo TextMate
+            _ => {
+                if importer.theme.foreground.is_none() {
+                    importer.theme.foreground = get("foreground").and_then(|c| importer.color(c));
+                }
+                if importer.theme.background.is_none() {
+                    importer.theme.background = get("background").and_then(|c| importer.color(c));
+                }
+                continue;
+            }
+        };
+        for scope in scopes {
+            importer.add_rule(&scope, get("foreground"), get("background"), get("fontStyle"));
+        }
+    }
+
+    let name = json.get("name").and_then(JsonValue::as_str).map(str::to_string);
+    Ok(importer.finish(name))
+}
+
+fn import_textmate(source: &str) -> RunixResult<ImportedTheme> {
+    let plist = plist::Value::from_reader_xml(source.as_bytes())
+        .map_err(|e| RunixError::InvalidArgument(format!("tmTheme: {}", e)))?;
+    let root = plist.as_dictionary()
+        .ok_or_else(|| RunixError::InvalidArgument("tmTheme: root is not a dictionary".to_string()))?;
+
+    let mut importer = ScopeImporter::default();
+    let rules = root.get("settings").and_then(plist::Value::as_array).map(Vec::as_slice).unwrap_or_default();
+    for rule in rules.iter().filter_map(plist::Value::as_dictionary) {
+        let settings = rule.get("settings").and_then(plist::Value::as_dictionary);
+        let get = |key: &str| settings.and_then(|s| s.get(key)).and_then(plist::Value::as_string);
+        match rule.get("scope").and_then(plist::Value::as_string) {
+            Some(scope) => importer.add_rule(scope, get("foreground"), get("background"), get("fontStyle")),
+            None => {
+                importer.theme.foreground = get("foreground").and_then(|c| importer.color(c));
+                importer.theme.background = get("background").and_then(|c| importer.color(c));
+            }
+        }
+    }
+
+    let name = root.get("name").and_then(plist::Value::as_string).map(str::to_string);
+    Ok(importer.finish(name))
+}
+
+fn import_helix(source: &str) -> RunixResult<ImportedTheme> {
+    let table: toml::Table = toml::from_str(source)
+        .map_err(|e| RunixError::InvalidArgument(format!("Helix theme: {}", e)))?;
+
+    let palette: HashMap<String, String> = table.get("palette")
+        .and_then(toml::Value::as_table)
+        .map(|p| p.iter().filter_map(|(k, v)| v.as_str().map(|c| (k.clone(), c.to_string()))).collect())
+        .unwrap_or_default();
+
+    let mut theme = Theme::default();
+    let mut unknown = BTreeSet::new();
+    let mut warnings = Vec::new();
+    let color = |value: Option<&toml::Value>, warnings: &mut Vec<String>| -> Option<String> {
+        let name = value?.as_str()?;
+        let resolved = palette.get(name).map(String::as_str).unwrap_or(name);
+        let normalized = css_to_android_color(resolved);
+        if normalized.is_none() {
+            warnings.push(format!("unsupported color '{}'", name));
+        }
+        normalized
+    };
+
+    if let Some(parent) = table.get("inherits") {
+        let parent = parent.as_str().unwrap_or_default();
+        warnings.push(format!("inherited theme '{}' is not imported; only keys defined here are used", parent));
+    }
+
+    for (key, value) in table.iter().filter(|(k, _)| k.as_str() != "palette" && k.as_str() != "inherits") {
+        let (fg, bg, modifiers) = match value {
+            toml::Value::String(_) => (color(Some(value), &mut warnings), None, Vec::new()),
+            toml::Value::Table(t) => (
+                color(t.get("fg"), &mut warnings),
+                color(t.get("bg"), &mut warnings),
+                t.get("modifiers").and_then(toml::Value::as_array).map(|m| {
+                    m.iter().filter_map(toml::Value::as_str).collect::<Vec<_>>()
+                }).unwrap_or_default(),
+            ),
+            _ => {
+                warnings.push(format!("unsupported value for '{}'", key));
+                continue;
+            }
+        };
+
+        match key.as_str() {
+            "ui.background" => theme.background = bg.or(fg),
+            "ui.text" => theme.foreground = fg,
+            // El resto de claves ui.*, diagnostic.*, diff.* y markup.* no son capturas de código
+            k if ["ui", "diagnostic", "diff", "markup", "warning", "error", "info", "hint"]
+                .iter().any(|p| k == *p || k.starts_with(&format!("{}.", p))) => {
+                unknown.insert(key.clone());
+            }
+            k if !CAPTURE_FAMILIES.contains(&k.split('.').next().unwrap_or(k)) => {
+                unknown.insert(key.clone());
+            }
+            _ => {
+                let style = Style {
+                    foreground: fg,
+                    background: bg,
+                    bold: modifiers.contains(&"bold"),
+                    italic: modifiers.contains(&"italic"),
+                    underline: modifiers.iter().any(|m| m.starts_with("underline")),
+                };
+                theme.theme.insert(key.clone(), ThemeEntry::Style(style));
+            }
+        }
+    }
+
+    let importer = ScopeImporter { theme, unknown, warnings };
+    Ok(importer.finish(None))
+}
+
+/// Elimina comentarios `//` y `/* */` y comas finales de un JSONC, respetando las cadenas.
+pub fn strip_jsonc(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if c == '\\' {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for next in chars.by_ref() {
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
+                }
+            }
+            ']' | '}' => {
+                // Quitar una coma final pendiente antes del cierre
+                let trimmed_len = out.trim_end().len();
+                if out[..trimmed_len].ends_with(',') {
+                    out.truncate(trimmed_len - 1);
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_vscode_token_colors() {
+        let source = r##"{
+            // comentario
+            "name": "Demo",
+            "colors": { "editor.foreground": "#ABB2BF", "editor.background": "#282C34" },
+            "tokenColors": [
+                { "scope": ["keyword.control", "storage.type"], "settings": { "foreground": "#C678DD", "fontStyle": "italic" } },
+                { "scope": "support.function", "settings": { "foreground": "#56B6C280" } },
+                { "scope": "meta.embedded.weird", "settings": { "foreground": "#FFFFFF" } },
+            ]
+        }"##;
+        let imported = import_theme(source, None).unwrap();
+
+        assert_eq!(imported.name.as_deref(), Some("Demo"));
+        assert_eq!(imported.theme.background.as_deref(), Some("#282C34"));
+        assert!(matches!(imported.theme.theme.get("keyword"), Some(ThemeEntry::Style(s)) if s.italic));
+        assert!(imported.theme.theme.contains_key("type"));
+        assert_eq!(imported.theme.theme.get("function.builtin"), Some(&ThemeEntry::Color("#8056B6C2".to_string())));
+        assert_eq!(imported.unknown_scopes, vec!["meta.embedded.weird".to_string()]);
+        assert_eq!(css_to_android_color("#ééé"), None);
+    }
+
+    #[test]
+    fn imports_helix_palette() {
+        let source = r##"
+            "keyword" = { fg = "purple", modifiers = ["bold"] }
+            "function.builtin" = "cyan"
+            "ui.background" = { bg = "black" }
+            "ui.cursor" = { fg = "black" }
+            "namespace" = "cyan"
+            inherits = "onedark"
+
+            [palette]
+            purple = "#c678dd"
+            cyan = "#56b6c2"
+            black = "#282c34"
+        "##;
+        let imported = import_theme(source, Some(ThemeFormat::Helix)).unwrap();
+
+        assert_eq!(imported.theme.background.as_deref(), Some("#282C34"));
+        assert_eq!(imported.theme.theme.get("function.builtin"), Some(&ThemeEntry::Color("#56B6C2".to_string())));
+        assert_eq!(imported.unknown_scopes, vec!["namespace".to_string(), "ui.cursor".to_string()]);
+        assert!(!imported.theme.theme.contains_key("namespace"));
+        assert_eq!(imported.warnings.len(), 1);
+        assert!(imported.warnings[0].contains("onedark"));
+    }
+}
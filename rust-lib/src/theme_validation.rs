@@ -0,0 +1,158 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::theme::parse_color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProblemSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    JsonSyntax,
+    InvalidStructure,
+    InvalidColor,
+    UnknownCapture,
+    MissingBaseColor,
+}
+
+/// Un problema encontrado al validar un tema.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThemeProblem {
+    pub kind: ProblemKind,
+    pub severity: ProblemSeverity,
+    pub message: String,
+    /// Clave del tema afectada, si la hay
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Posición (1-based) del error de sintaxis JSON
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl ThemeProblem {
+    fn new(kind: ProblemKind, severity: ProblemSeverity, message: String, key: Option<&str>) -> Self {
+        Self { kind, severity, message, key: key.map(str::to_string), line: None, column: None }
+    }
+}
+
+/// Valida un tema en cualquiera de los formatos que acepta `Theme::from_json`.
+/// `highlight_names` son los nombres que el editor puede producir; una clave que ninguno de
+/// ellos alcanza por caída jerárquica nunca se usará.
+pub fn validate_theme(json: &str, highlight_names: &[String]) -> Vec<ThemeProblem> {
+    let mut problems = Vec::new();
+    let value: Value = match serde_json::from_str(json) {
+        Ok(v) => v,
+        Err(e) => {
+            problems.push(ThemeProblem {
+                line: Some(e.line()),
+                column: Some(e.column()),
+                ..ThemeProblem::new(ProblemKind::JsonSyntax, ProblemSeverity::Error, e.to_string(), None)
+            });
+            return problems;
+        }
+    };
+
+    let Some(root) = value.as_object() else {
+        problems.push(ThemeProblem::new(ProblemKind::InvalidStructure, ProblemSeverity::Error,
+            "theme must be a JSON object".to_string(), None));
+        return problems;
+    };
+
+    // Formato envuelto {"theme": {...}} o mapa plano con los colores base mezclados
+    let (entries, base): (&Map<String, Value>, &Map<String, Value>) = match root.get("theme") {
+        Some(Value::Object(entries)) => (entries, root),
+        Some(_) => {
+            problems.push(ThemeProblem::new(ProblemKind::InvalidStructure, ProblemSeverity::Error,
+                "\"theme\" must be an object".to_string(), Some("theme")));
+            return problems;
+        }
+        None => (root, root),
+    };
+
+    for (key, value) in entries {
+        if std::ptr::eq(entries, base) && (key == "foreground" || key == "background") {
+            continue;
+        }
+        check_entry(key, value, &mut problems);
+        if !highlight_names.iter().any(|name| name == key || name.starts_with(&format!("{}.", key))) {
+            problems.push(ThemeProblem::new(ProblemKind::UnknownCapture, ProblemSeverity::Warning,
+                format!("'{}' does not match any highlight name", key), Some(key)));
+        }
+    }
+
+    for base_key in ["foreground", "background"] {
+        match base.get(base_key) {
+            Some(Value::String(color)) => check_color(base_key, color, &mut problems),
+            Some(_) => problems.push(ThemeProblem::new(ProblemKind::InvalidStructure, ProblemSeverity::Error,
+                format!("'{}' must be a color string", base_key), Some(base_key))),
+            None => problems.push(ThemeProblem::new(ProblemKind::MissingBaseColor, ProblemSeverity::Warning,
+                format!("missing base color '{}'", base_key), Some(base_key))),
+        }
+    }
+
+    problems
+}
+
+fn check_entry(key: &str, value: &Value, problems: &mut Vec<ThemeProblem>) {
+    match value {
+        Value::String(color) => check_color(key, color, problems),
+        Value::Object(style) => {
+            for (field, field_value) in style {
+                match (field.as_str(), field_value) {
+                    ("foreground" | "fg" | "color" | "background" | "bg", Value::String(color)) => {
+                        check_color(key, color, problems)
+                    }
+                    ("bold" | "italic" | "underline", Value::Bool(_)) => {}
+                    _ => problems.push(ThemeProblem::new(ProblemKind::InvalidStructure, ProblemSeverity::Error,
+                        format!("unexpected field '{}' in style for '{}'", field, key), Some(key))),
+                }
+            }
+        }
+        _ => problems.push(ThemeProblem::new(ProblemKind::InvalidStructure, ProblemSeverity::Error,
+            format!("'{}' must be a color string or a style object", key), Some(key))),
+    }
+}
+
+fn check_color(key: &str, color: &str, problems: &mut Vec<ThemeProblem>) {
+    if parse_color(color).is_none() {
+        problems.push(ThemeProblem::new(ProblemKind::InvalidColor, ProblemSeverity::Error,
+            format!("invalid color '{}' for '{}' (expected #RGB, #RRGGBB or #AARRGGBB)", color, key), Some(key)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        vec!["keyword".to_string(), "function.builtin".to_string()]
+    }
+
+    #[test]
+    fn reports_syntax_error_position() {
+        let problems = validate_theme("{\n  \"keyword\": \"#fff\",\n}", &names());
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::JsonSyntax);
+        assert_eq!(problems[0].line, Some(3));
+    }
+
+    #[test]
+    fn reports_colors_captures_and_base() {
+        let json = r##"{"theme": {"keyword": "#GGG", "function": "#61AFEF", "tag": "#E06C75"}, "background": "#282C34"}"##;
+        let kinds: Vec<(ProblemKind, Option<String>)> = validate_theme(json, &names())
+            .into_iter().map(|p| (p.kind, p.key)).collect();
+
+        assert!(kinds.contains(&(ProblemKind::InvalidColor, Some("keyword".to_string()))));
+        assert!(kinds.contains(&(ProblemKind::UnknownCapture, Some("tag".to_string()))));
+        assert!(kinds.contains(&(ProblemKind::MissingBaseColor, Some("foreground".to_string()))));
+        // "function" se usa como caída de "function.builtin"
+        assert!(!kinds.contains(&(ProblemKind::UnknownCapture, Some("function".to_string()))));
+    }
+}
@@ -1,13 +1,84 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tree_sitter::Tree;
+use crate::offsets::{OffsetEncoding, TextPoint};
+use crate::theme::ResolvedStyle;
 
 
+// Consultas .scm que definen un lenguaje para el resaltador
+#[derive(Debug, Clone, Copy)]
+pub struct HighlightQueries<'a> {
+    pub highlights: &'a str,
+    pub injections: &'a str,
+    pub locals: &'a str
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct HighlightRange {
     pub start: usize,
     pub end: usize,
-    pub highlight_type: usize
+    pub highlight_type: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_point: Option<TextPoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_point: Option<TextPoint>,
+    // Id de estilo o color ARGB, según `HighlightOptions::style_output`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<u32>
+}
+
+impl HighlightRange {
+    pub fn new(start: usize, end: usize, highlight_type: usize) -> Self {
+        Self { start, end, highlight_type, start_point: None, end_point: None, style: None }
+    }
+}
+
+// Qué información de estilo acompaña a cada rango
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StyleOutput {
+    #[default]
+    None,
+    // Índice en `HighlightDelta::styles`
+    Ids,
+    // Color de primer plano ARGB resuelto
+    Argb
+}
+
+/// Reemplazo de `start..end` (offsets del texto original) por `text`. Las listas de edits
+/// no se solapan y van ordenadas por `start`, como en LSP.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String
+}
+
+impl TextEdit {
+    pub fn new(start: usize, end: usize, text: impl Into<String>) -> Self {
+        Self { start, end, text: text.into() }
+    }
+}
+
+/// Aplica edits sin solapes, con offsets del texto original, como `Document::apply_edits`.
+#[cfg(test)]
+pub(crate) fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|edit| edit.start);
+    let mut result = text.to_string();
+    for edit in sorted.into_iter().rev() {
+        result.replace_range(edit.start..edit.end, &edit.text);
+    }
+    result
+}
+
+// Opciones de salida que Kotlin puede pedir para el resaltado (JSON, todos los campos opcionales)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HighlightOptions {
+    pub offset_encoding: OffsetEncoding,
+    pub include_points: bool,
+    pub style_output: StyleOutput
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -16,7 +87,9 @@ pub struct HighlightDelta {
     pub highlight_names: Vec<String>,
     pub reused_ranges: Option<Vec<(usize, usize)>>, // Rangos que se pueden reutilizar
     pub version: u64,
-    pub changed_ranges: Vec<(usize, usize)> // Rangos que cambiaron desde la última versión
+    pub changed_ranges: Vec<(usize, usize)>, // Rangos que cambiaron desde la última versión
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub styles: Option<Vec<ResolvedStyle>> // Tabla de estilos cuando se piden ids
 }
 
 // Eliminar duplicidad de HighlightCacheStore, ya que HighlightCache está en cache.rs
@@ -31,6 +104,12 @@ pub struct IncrementalState {
     pub input_length: usize
 }
 
+impl Default for IncrementalState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IncrementalState {
     pub fn new() -> Self {
         Self {